@@ -1,9 +1,13 @@
 #!/bin/sh
 
 examples=$(find examples -maxdepth 1 -type f -name '*.rs' -exec basename {} .rs \;)
+# Every example also has to work when rank 0 is the only process
+processes=${PROCESSES:-"1 4"}
 
 for example in $examples; do
-    echo "Running example: $example"
-    cargo mpirun --release --example $example
-    echo ""
+    for n in $processes; do
+        echo "Running example: $example with $n processes"
+        cargo mpirun -n $n --release --example $example
+        echo ""
+    done
 done
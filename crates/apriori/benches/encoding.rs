@@ -0,0 +1,102 @@
+//! The bytes that Count Distribution sends per pass with to_vec and with to_bytes.
+//! Run with `cargo bench -p apriori --bench encoding -- [file] [support] [ranks]`.
+
+use std::{env, fs::File, time::Instant};
+
+use apriori::{
+    apriori::{apriori_pass_one, apriori_pass_three_counter, apriori_pass_two_counter},
+    array2d::AprioriP2Counter,
+    storage::{AprioriCounter, AprioriFrequent},
+    threaded::split,
+    transaction_set::TransactionSet,
+    trie::{TrieCounter, TrieSet},
+};
+use parallel::{encoding::encode, traits::Convertable};
+
+/// The bytes of the u64 vectors and of their compact encodings, and the encoding time
+struct Sent {
+    raw: usize,
+    compact: usize,
+    secs: f64,
+}
+impl Sent {
+    fn new() -> Self {
+        Self {
+            raw: 0,
+            compact: 0,
+            secs: 0.0,
+        }
+    }
+    fn add(&mut self, v: &[u64]) {
+        let start = Instant::now();
+        self.compact += encode(v).len();
+        self.secs += start.elapsed().as_secs_f64();
+        self.raw += v.len() * 8;
+    }
+    fn print(&self, pass: usize) {
+        println!(
+            "{pass:>4} {:>12} {:>12} {:>7.1}% {:>10.6}",
+            self.raw,
+            self.compact,
+            100.0 * (1.0 - self.compact as f64 / self.raw.max(1) as f64),
+            self.secs
+        );
+    }
+}
+
+fn main() {
+    // cargo bench passes --bench, which is not an argument of this benchmark
+    let args: Vec<String> = env::args().skip(1).filter(|a| a != "--bench").collect();
+    let file = args
+        .first()
+        .cloned()
+        .unwrap_or(concat!(env!("CARGO_MANIFEST_DIR"), "/../../test_files/test1.dat").to_string());
+    let sup: u64 = args
+        .get(1)
+        .map_or(10, |s| s.parse().expect("Invalid support"));
+    let ranks: usize = args.get(2).map_or(4, |s| s.parse().expect("Invalid ranks"));
+    let data = TransactionSet::from_dat(File::open(&file).expect("Invalid file"))
+        .expect("Invalid database");
+    let parts = split(&data, ranks);
+    println!("{file} with support {sup} over {ranks} ranks");
+    println!("pass      to_vec B   to_bytes B    saved   encode s");
+
+    // Pass 2 sends the frequent items out and a pair counter back from each helper
+    let p1 = apriori_pass_one(&data, sup);
+    if p1.is_empty() {
+        return;
+    }
+    let mut sent = Sent::new();
+    let items: Vec<u64> = p1.iter().map(|&n| n as u64).collect();
+    let mut total = AprioriP2Counter::new(&p1);
+    for part in &parts {
+        sent.add(&items);
+        let mut counter = AprioriP2Counter::new(&p1);
+        apriori_pass_two_counter(part, &mut counter);
+        let v = counter.to_vec();
+        sent.add(&v);
+        total.add_from_vec(&v);
+    }
+    sent.print(2);
+    let mut prev: TrieSet = total.to_frequent_new(sup);
+
+    // The later passes send the frequent itemsets out and a trie counter back
+    for n in 3.. {
+        if prev.is_empty() {
+            break;
+        }
+        let mut sent = Sent::new();
+        let candidates = prev.to_vec();
+        let mut total: TrieCounter = prev.join_new();
+        for part in &parts {
+            sent.add(&candidates);
+            let mut counter: TrieCounter = prev.join_new();
+            apriori_pass_three_counter(part, &mut counter, n);
+            let v = counter.to_vec();
+            sent.add(&v);
+            total.add_from_vec(&v);
+        }
+        sent.print(n);
+        prev = total.to_frequent_new(sup);
+    }
+}
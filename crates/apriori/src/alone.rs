@@ -44,10 +44,10 @@ impl AprioriTrie {
             }
             prev.filter(k, self.sup);
             let mut count = 0;
-            prev.for_each(
-                |v| {
+            prev.for_each_count(
+                |v, c| {
                     count += 1;
-                    out.write_set(v);
+                    out.write_set_with_support(v, c);
                 },
                 k,
             );
@@ -127,6 +127,11 @@ impl Trie {
     }
 
     pub fn for_each(&self, mut f: impl FnMut(&[usize]), k: usize) {
+        self.for_each_count(|v, _| f(v), k);
+    }
+
+    /// Like for_each, but also passes the count of each set
+    pub fn for_each_count(&self, mut f: impl FnMut(&[usize], u64), k: usize) {
         let mut v = Vec::new();
         self.root.for_each(&mut f, &mut v, k);
     }
@@ -150,9 +155,9 @@ impl Node {
         }
     }
 
-    fn for_each(&self, f: &mut impl FnMut(&[usize]), v: &mut Vec<usize>, k: usize) {
+    fn for_each(&self, f: &mut impl FnMut(&[usize], u64), v: &mut Vec<usize>, k: usize) {
         if k == 0 {
-            f(v);
+            f(v, self.count);
             return;
         }
         for (&a, child) in self.children.iter() {
@@ -164,7 +169,7 @@ impl Node {
 
     fn join(&self, v: &mut Vec<usize>, f: &mut impl FnMut(&[usize]), k: usize) {
         if v.len() == k - 1 {
-            let a: Vec<_> = self.children.iter().map(|(k, _)| *k).collect();
+            let a: Vec<_> = self.children.keys().copied().collect();
             for (i, &n1) in a.iter().enumerate() {
                 for &n2 in a.iter().skip(i + 1) {
                     let max = n1.max(n2);
@@ -1,12 +1,13 @@
 use std::time::Instant;
 
 use crate::array2d::AprioriP2Counter;
+use crate::checkpoint::{Checkpoint, Checkpoints, CountingWriter};
 use crate::start::Apriori;
 use crate::storage::{AprioriCounter, AprioriCounting, AprioriFrequent};
 use crate::trie::{TrieCounter, TrieSet};
 use crate::{start::Write, transaction_set::TransactionSet};
 
-pub fn apriori_pass_one_counter(data: &TransactionSet, counter: &mut impl AprioriCounter) {
+pub fn apriori_pass_one_counter(data: &[Vec<usize>], counter: &mut impl AprioriCounter) {
     for d in data.iter() {
         for &n in d.iter() {
             counter.increment(&[n]);
@@ -24,38 +25,84 @@ pub fn apriori_pass_one(data: &TransactionSet, sup: u64) -> Vec<usize> {
         .collect()
 }
 
+/// Like apriori_pass_one, but also writes the frequent items with their supports
+pub fn apriori_pass_one_write(data: &TransactionSet, sup: u64, out: &mut impl Write) -> Vec<usize> {
+    let mut counter = vec![0; data.num_items];
+    apriori_pass_one_counter(data, &mut counter);
+    let mut p1 = Vec::new();
+    for (i, &count) in counter.iter().enumerate() {
+        if count >= sup {
+            out.write_set_with_support(&[i], count);
+            p1.push(i);
+        }
+    }
+    p1
+}
+
 pub struct AprioriRunner<'a> {
     data: &'a TransactionSet,
     sup: u64,
+    checkpoints: Option<Checkpoints>,
+    resume: Option<Checkpoint>,
 }
 
 impl Apriori for AprioriRunner<'_> {
     fn run<T: Write>(self, out: &mut T) {
-        let p1 = apriori_pass_one(self.data, self.sup);
-        p1.iter().for_each(|&n| out.write_set(&[n]));
-        let mut prev: TrieSet = apriori_pass_two(self.data, self.sup, &p1);
-        prev.for_each(|v| out.write_set(v));
-        for i in 3.. {
+        let mut out = CountingWriter::new(out, self.resume.as_ref().map_or(0, |c| c.written));
+        let (mut prev, start) = match self.resume {
+            // The checkpoint of the last pass has nothing left to join
+            Some(checkpoint) if checkpoint.frequent.is_empty() => return,
+            Some(checkpoint) => (checkpoint.frequent, checkpoint.pass + 1),
+            None => {
+                let p1 = apriori_pass_one_write(self.data, self.sup, &mut out);
+                let mut counter = AprioriP2Counter::new(&p1);
+                apriori_pass_two_counter(self.data, &mut counter);
+                let mut prev: TrieSet = counter.write_frequent(self.sup, &mut out);
+                if let Some(c) = &self.checkpoints {
+                    c.save(2, self.sup, &mut prev, &mut out);
+                }
+                (prev, 3)
+            }
+        };
+        for i in start.. {
             let prev_time = Instant::now();
-            prev = apriori_pass_three::<_, TrieCounter>(self.data, &prev, i, self.sup);
+            let mut counter: TrieCounter = prev.join_new();
+            apriori_pass_three_counter(self.data, &mut counter, i);
+            prev = counter.write_frequent(self.sup, &mut out);
             println!("{i} {:?}", prev_time.elapsed());
+            if let Some(c) = &self.checkpoints {
+                c.save(i, self.sup, &mut prev, &mut out);
+            }
             if prev.is_empty() {
                 break;
             }
-            prev.for_each(|v| {
-                out.write_set(v);
-            });
         }
     }
 }
 
 impl<'a> AprioriRunner<'a> {
     pub fn new(data: &'a TransactionSet, sup: u64) -> Self {
-        Self { data, sup }
+        Self {
+            data,
+            sup,
+            checkpoints: None,
+            resume: None,
+        }
+    }
+    /// Saves a checkpoint after every pass from 2 on
+    pub fn with_checkpoints(mut self, checkpoints: Checkpoints) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+    /// Continues after the pass of checkpoint, which has to be made with the same support.
+    /// out already has the itemsets written up to it.
+    pub fn with_resume(mut self, checkpoint: Checkpoint) -> Self {
+        self.resume = Some(checkpoint);
+        self
     }
 }
 
-pub fn apriori_pass_two_counter(data: &TransactionSet, counter: &mut impl AprioriCounter) {
+pub fn apriori_pass_two_counter(data: &[Vec<usize>], counter: &mut impl AprioriCounter) {
     for d in data.iter() {
         for (i, a) in d.iter().cloned().enumerate() {
             for b in d.iter().cloned().skip(i + 1) {
@@ -74,7 +121,7 @@ pub fn apriori_pass_two<T: AprioriFrequent + Default>(
     counter.to_frequent_new::<T>(sup)
 }
 pub fn apriori_pass_three_counter<T: AprioriCounting>(
-    data: &TransactionSet,
+    data: &[Vec<usize>],
     counter: &mut T,
     n: usize,
 ) {
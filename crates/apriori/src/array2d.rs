@@ -33,14 +33,12 @@ impl Convertable for AprioriP2Counter<'_> {
 }
 impl AprioriCounter for AprioriP2Counter<'_> {
     fn increment(&mut self, v: &[usize]) -> bool {
-        if let (Some(a), Some(b)) = (
+        if let (Some(Some(a)), Some(Some(b))) = (
             self.reverse_map.get(v[0]).cloned(),
             self.reverse_map.get(v[1]).cloned(),
         ) {
-            if let (Some(a), Some(b)) = (a, b) {
-                self.arr.increment(a, b);
-                return true;
-            }
+            self.arr.increment(a, b);
+            return true;
         }
         false
     }
@@ -50,13 +48,11 @@ impl AprioriCounter for AprioriP2Counter<'_> {
     }
 
     fn get_count(&self, v: &[usize]) -> Option<u64> {
-        if let (Some(a), Some(b)) = (
+        if let (Some(Some(a)), Some(Some(b))) = (
             self.reverse_map.get(v[0]).cloned(),
             self.reverse_map.get(v[1]).cloned(),
         ) {
-            if let (Some(a), Some(b)) = (a, b) {
-                return Some(self.arr.get(a, b));
-            }
+            return Some(self.arr.get(a, b));
         }
         None
     }
@@ -103,7 +99,7 @@ impl<T> Array2D<T> {
         self.0[index] = value;
     }
     /// Iterator over all the element of the 2D array.
-    pub fn iter(&self) -> Array2DIterator<T> {
+    pub fn iter(&self) -> Array2DIterator<'_, T> {
         Array2DIterator::new(self)
     }
     pub fn len(&self) -> usize {
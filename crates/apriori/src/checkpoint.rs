@@ -0,0 +1,156 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+use parallel::{
+    encoding::{decode, encode},
+    traits::Convertable,
+};
+
+use crate::{start::Write, trie::TrieSet};
+
+/// The progress of a runner after a completed pass
+pub struct Checkpoint {
+    pub pass: usize,
+    /// The number of itemsets written up to and including the pass
+    pub written: u64,
+    pub sup: u64,
+    /// The frequent itemsets of the pass
+    pub frequent: TrieSet,
+}
+
+/// The directory where a runner saves a checkpoint after each pass
+#[derive(Debug, Clone)]
+pub struct Checkpoints {
+    dir: PathBuf,
+}
+
+impl Checkpoints {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+    fn path(&self) -> PathBuf {
+        self.dir.join("checkpoint")
+    }
+    /// The last saved checkpoint, or None if there is none yet
+    pub fn load(&self) -> io::Result<Option<Checkpoint>> {
+        let bytes = match fs::read(self.path()) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let v = decode(&bytes).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        let [pass, written, sup, trie @ ..] = &v[..] else {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Truncated checkpoint",
+            ));
+        };
+        let frequent = TrieSet::from_vec(trie).ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidData, "Corrupt itemsets in the checkpoint")
+        })?;
+        Ok(Some(Checkpoint {
+            pass: *pass as usize,
+            written: *written,
+            sup: *sup,
+            frequent,
+        }))
+    }
+    /// Whether a checkpoint has been saved here
+    pub fn exists(&self) -> bool {
+        self.path().exists()
+    }
+    /// Saves frequent as the itemsets of pass, after flushing out.
+    /// The previous checkpoint only gets replaced once the new one is complete.
+    /// A failed save is reported without stopping the run.
+    pub fn save<T: Write>(
+        &self,
+        pass: usize,
+        sup: u64,
+        frequent: &mut TrieSet,
+        out: &mut CountingWriter<T>,
+    ) {
+        out.flush_sets();
+        let mut v = vec![pass as u64, out.written(), sup];
+        v.extend(frequent.to_vec());
+        let tmp = self.dir.join("checkpoint.tmp");
+        let saved = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&tmp, encode(&v)))
+            .and_then(|_| fs::rename(&tmp, self.path()));
+        if let Err(e) = saved {
+            eprintln!("Failed to save the checkpoint of pass {pass}: {e}");
+        }
+    }
+}
+
+/// Counts the itemsets written to the inner writer, for the offset of the checkpoints
+pub struct CountingWriter<'a, T: Write> {
+    inner: &'a mut T,
+    written: u64,
+}
+
+impl<'a, T: Write> CountingWriter<'a, T> {
+    /// written is the number of itemsets that the inner writer already has
+    pub fn new(inner: &'a mut T, written: u64) -> Self {
+        Self { inner, written }
+    }
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+    pub fn into_inner(self) -> &'a mut T {
+        self.inner
+    }
+}
+
+impl<T: Write> Write for CountingWriter<'_, T> {
+    fn write_set_with_support(&mut self, v: &[usize], sup: u64) {
+        self.written += 1;
+        self.inner.write_set_with_support(v, sup);
+    }
+    fn flush_sets(&mut self) {
+        self.inner.flush_sets();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, io::ErrorKind};
+
+    use parallel::encoding::encode;
+
+    use crate::{start::Write, storage::AprioriFrequent, trie::TrieSet};
+
+    use super::{Checkpoints, CountingWriter};
+
+    #[test]
+    fn test_checkpoint() {
+        let dir = env::temp_dir().join(format!("apriori_checkpoint_{}", std::process::id()));
+        let checkpoints = Checkpoints::new(&dir);
+        assert!(!checkpoints.exists());
+        assert!(checkpoints.load().unwrap().is_none());
+        let mut frequent = TrieSet::new();
+        frequent.insert(&[1, 2, 3]);
+        frequent.insert(&[1, 2, 5]);
+        let mut out = Vec::new();
+        let mut writer = CountingWriter::new(&mut out, 4);
+        frequent.for_each(|v| writer.write_set_with_support(v, 10));
+        checkpoints.save(3, 10, &mut frequent, &mut writer);
+        assert!(checkpoints.exists());
+        let checkpoint = checkpoints.load().unwrap().unwrap();
+        assert_eq!(checkpoint.sup, 10);
+        assert_eq!(checkpoint.pass, 3);
+        assert_eq!(checkpoint.written, 6);
+        assert_eq!(checkpoint.frequent.len(), 2);
+        assert!(checkpoint.frequent.contains(&[1, 2, 3]));
+        assert!(checkpoint.frequent.contains(&[1, 2, 5]));
+        // Empty, truncated and corrupt checkpoints
+        for bytes in [vec![], encode(&[3, 6, 10]), encode(&[3, 6, 10, 3, 2, 1])] {
+            fs::write(dir.join("checkpoint"), bytes).unwrap();
+            let e = checkpoints.load().err().unwrap();
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
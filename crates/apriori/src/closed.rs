@@ -0,0 +1,186 @@
+use ahash::AHashSet;
+
+use crate::{
+    start::{Apriori, Write},
+    storage::AprioriCounter,
+    transaction_set::TransactionSet,
+    trie::TrieCounter,
+};
+
+/// Writes the closed itemsets of counter.
+/// counter must hold every frequent itemset with its support.
+/// An itemset is closed when no superset has the same support,
+/// so it is enough to check the supersets with one more item.
+pub fn write_closed(counter: &TrieCounter, out: &mut impl Write) {
+    let mut not_closed = AHashSet::new();
+    let mut subset = Vec::new();
+    counter.for_each(|v, c| {
+        if v.len() < 2 {
+            return;
+        }
+        for i in 0..v.len() {
+            subset.clear();
+            subset.extend(
+                v.iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, &n)| n),
+            );
+            if counter.get_count(&subset) == Some(c) {
+                not_closed.insert(subset.clone());
+            }
+        }
+    });
+    counter.for_each(|v, c| {
+        if !v.is_empty() && !not_closed.contains(v) {
+            out.write_set_with_support(v, c);
+        }
+    });
+}
+
+/// Collects the output of any runner so only the closed itemsets get written.
+#[derive(Default)]
+pub struct ClosedWriter {
+    counter: TrieCounter,
+}
+
+impl ClosedWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Writes the closed itemsets to out
+    pub fn finish(self, out: &mut impl Write) {
+        write_closed(&self.counter, out);
+    }
+}
+
+impl Write for ClosedWriter {
+    fn write_set_with_support(&mut self, v: &[usize], sup: u64) {
+        self.counter.add(v, sup);
+    }
+}
+
+/// Mines the closed frequent itemsets directly with LCM.
+/// Each closed itemset is reached once from its prefix-preserving parent,
+/// so no duplicate or subsumption checks are needed.
+pub struct ClosedRunner<'a> {
+    data: &'a TransactionSet,
+    sup: u64,
+}
+
+impl<'a> ClosedRunner<'a> {
+    pub fn new(data: &'a TransactionSet, sup: u64) -> Self {
+        Self { data, sup }
+    }
+    /// Counts how many of the transactions in tids contain each item
+    fn count(&self, tids: &[usize]) -> Vec<u64> {
+        let mut counts = vec![0; self.data.num_items];
+        for &t in tids {
+            for &n in &self.data[t] {
+                counts[n] += 1;
+            }
+        }
+        counts
+    }
+    /// The items shared by every transaction in tids
+    fn closure(&self, tids: &[usize]) -> Vec<usize> {
+        self.count(tids)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, c)| *c == tids.len() as u64)
+            .map(|(n, _)| n)
+            .collect()
+    }
+    /// Writes every closed itemset reachable from the closed itemset p.
+    /// tids are the transactions that contain p and only items after core extend p.
+    fn expand<T: Write>(&self, p: &[usize], tids: &[usize], core: Option<usize>, out: &mut T) {
+        let counts = self.count(tids);
+        let start = core.map_or(0, |c| c + 1);
+        for (e, &count) in counts.iter().enumerate().skip(start) {
+            if count < self.sup || p.binary_search(&e).is_ok() {
+                continue;
+            }
+            let next: Vec<usize> = tids
+                .iter()
+                .copied()
+                .filter(|&t| self.data[t].binary_search(&e).is_ok())
+                .collect();
+            let q = self.closure(&next);
+            // The closure must not add any item before e
+            if q.iter()
+                .take_while(|&&n| n < e)
+                .any(|n| p.binary_search(n).is_err())
+            {
+                continue;
+            }
+            out.write_set_with_support(&q, next.len() as u64);
+            self.expand(&q, &next, Some(e), out);
+        }
+    }
+}
+
+impl Apriori for ClosedRunner<'_> {
+    fn run<T: Write>(self, out: &mut T) {
+        if (self.data.len() as u64) < self.sup {
+            return;
+        }
+        let tids: Vec<usize> = (0..self.data.len()).collect();
+        let root = self.closure(&tids);
+        if !root.is_empty() {
+            out.write_set_with_support(&root, tids.len() as u64);
+        }
+        self.expand(&root, &tids, None, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        apriori::AprioriRunner,
+        start::{Apriori, FrequentWriter},
+        transaction_set::TransactionSet,
+    };
+
+    use super::{ClosedRunner, ClosedWriter};
+
+    fn closed(v: &[&[usize]]) -> HashSet<Vec<usize>> {
+        v.iter().map(|v| v.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_closed_writer() {
+        let set = TransactionSet::new(vec![vec![1, 2, 3], vec![1, 2, 3], vec![2, 4]], 5);
+        let mut writer = ClosedWriter::new();
+        AprioriRunner::new(&set, 2).run(&mut writer);
+        let mut s = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        writer.finish(&mut s);
+        assert_eq!(s.into_inner(), closed(&[&[2], &[1, 2, 3]]));
+    }
+    #[test]
+    fn test_run_closed() {
+        let set = TransactionSet::new(
+            vec![
+                vec![0, 1, 2],
+                vec![0, 1],
+                vec![1, 2],
+                vec![0, 1, 2],
+                vec![3],
+            ],
+            4,
+        );
+        let mut s = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        ClosedRunner::new(&set, 2).run(&mut s);
+        assert_eq!(
+            s.into_inner(),
+            closed(&[&[1], &[0, 1], &[1, 2], &[0, 1, 2]])
+        );
+        let mut s = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        ClosedRunner::new(&set, 1).run(&mut s);
+        assert_eq!(
+            s.into_inner(),
+            closed(&[&[1], &[0, 1], &[1, 2], &[0, 1, 2], &[3]])
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    eclat::{TidList, intersect, vertical},
+    start::{Apriori, Write},
+    transaction_set::TransactionSet,
+};
+
+/// The density above which a class switches from tid lists to diffsets.
+/// Past 0.5 the diffset of a child is expected to be smaller than its tid list.
+const DENSITY_SWITCH: f64 = 0.5;
+
+/// The sorted difference a \ b of two sorted tid lists
+pub fn difference(a: &[usize], b: &[usize]) -> TidList {
+    let mut v = Vec::new();
+    let mut j = 0;
+    for &n in a {
+        while j < b.len() && b[j] < n {
+            j += 1;
+        }
+        if j >= b.len() || b[j] != n {
+            v.push(n);
+        }
+    }
+    v
+}
+
+/// A member of an equivalence class.
+/// cover is either the tid list of prefix + item or its diffset from the prefix,
+/// depending on the class it is in.
+#[derive(Debug)]
+struct Member {
+    item: usize,
+    cover: TidList,
+    sup: u64,
+}
+
+/// Mines the frequent itemsets depth-first like Eclat.
+/// Dense classes store diffsets, the transactions of the prefix that do not contain the itemset,
+/// instead of full tid lists.
+pub struct DEclat<'a> {
+    data: &'a TransactionSet,
+    sup: u64,
+}
+
+impl<'a> DEclat<'a> {
+    pub fn new(data: &'a TransactionSet, sup: u64) -> Self {
+        Self { data, sup }
+    }
+    /// Whether the class under a prefix with support prefix_sup is dense enough for diffsets.
+    fn is_dense(class: &[Member], prefix_sup: u64) -> bool {
+        if class.is_empty() || prefix_sup == 0 {
+            return false;
+        }
+        let total: u64 = class.iter().map(|m| m.sup).sum();
+        (total as f64) / (class.len() as f64 * prefix_sup as f64) > DENSITY_SWITCH
+    }
+    /// Mines every extension of prefix within its equivalence class.
+    /// diff tells whether the covers of class are diffsets.
+    fn mine<T: Write>(&self, prefix: &mut Vec<usize>, class: &[Member], diff: bool, out: &mut T) {
+        for (i, a) in class.iter().enumerate() {
+            prefix.push(a.item);
+            out.write_set_with_support(prefix, a.sup);
+            let rest = &class[(i + 1)..];
+            // Diffsets stay diffsets, tid lists switch once the class is dense
+            let next_diff = diff || Self::is_dense(rest, a.sup);
+            let mut next = Vec::new();
+            for b in rest {
+                let cover = if diff {
+                    difference(&b.cover, &a.cover)
+                } else if next_diff {
+                    difference(&a.cover, &b.cover)
+                } else {
+                    intersect(&a.cover, &b.cover)
+                };
+                let sup = if next_diff {
+                    a.sup - cover.len() as u64
+                } else {
+                    cover.len() as u64
+                };
+                if sup >= self.sup {
+                    next.push(Member {
+                        item: b.item,
+                        cover,
+                        sup,
+                    });
+                }
+            }
+            if !next.is_empty() {
+                self.mine(prefix, &next, next_diff, out);
+            }
+            prefix.pop();
+        }
+    }
+}
+
+impl Apriori for DEclat<'_> {
+    fn run<T: Write>(self, out: &mut T) {
+        let class: Vec<_> = vertical(self.data)
+            .into_iter()
+            .enumerate()
+            .map(|(item, cover)| Member {
+                item,
+                sup: cover.len() as u64,
+                cover,
+            })
+            .filter(|m| m.sup >= self.sup)
+            .collect();
+        let mut prefix = Vec::new();
+        self.mine(&mut prefix, &class, false, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::difference;
+
+    #[test]
+    fn test_difference() {
+        assert_eq!(difference(&[1, 3, 5, 7], &[2, 3, 4, 7, 9]), vec![1, 5]);
+        assert_eq!(difference(&[1, 2], &[]), vec![1, 2]);
+        assert_eq!(difference(&[], &[1, 2]), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{
+    start::{Apriori, Write},
+    transaction_set::TransactionSet,
+};
+
+/// A sorted list of transaction ids
+pub type TidList = Vec<usize>;
+
+/// Converts the database into its vertical layout.
+/// The i-th element is the list of transactions that contain item i.
+pub fn vertical(data: &TransactionSet) -> Vec<TidList> {
+    let mut tids = vec![Vec::new(); data.num_items];
+    for (tid, d) in data.iter().enumerate() {
+        for &n in d {
+            tids[n].push(tid);
+        }
+    }
+    tids
+}
+
+/// The intersection of two sorted tid lists
+pub fn intersect(a: &[usize], b: &[usize]) -> TidList {
+    let mut v = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                v.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    v
+}
+
+/// Mines the frequent itemsets depth-first by intersecting tid lists.
+pub struct Eclat<'a> {
+    data: &'a TransactionSet,
+    sup: u64,
+}
+
+impl<'a> Eclat<'a> {
+    pub fn new(data: &'a TransactionSet, sup: u64) -> Self {
+        Self { data, sup }
+    }
+}
+
+/// Mines every extension of prefix within its equivalence class.
+/// Each element of class is (item, tid list of prefix + item).
+pub fn mine_class<T: Write>(
+    sup: u64,
+    prefix: &mut Vec<usize>,
+    class: &[(usize, TidList)],
+    out: &mut T,
+) {
+    for (i, (a, tids_a)) in class.iter().enumerate() {
+        prefix.push(*a);
+        out.write_set_with_support(prefix, tids_a.len() as u64);
+        let mut next = Vec::new();
+        for (b, tids_b) in class.iter().skip(i + 1) {
+            let tids = intersect(tids_a, tids_b);
+            if tids.len() as u64 >= sup {
+                next.push((*b, tids));
+            }
+        }
+        if !next.is_empty() {
+            mine_class(sup, prefix, &next, out);
+        }
+        prefix.pop();
+    }
+}
+
+impl Apriori for Eclat<'_> {
+    fn run<T: Write>(self, out: &mut T) {
+        let class: Vec<_> = vertical(self.data)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, tids)| tids.len() as u64 >= self.sup)
+            .collect();
+        let mut prefix = Vec::new();
+        mine_class(self.sup, &mut prefix, &class, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transaction_set::TransactionSet;
+
+    use super::{intersect, vertical};
+
+    #[test]
+    fn test_vertical() {
+        let set = TransactionSet::new(vec![vec![0, 2], vec![1, 2], vec![2]], 3);
+        let v = vertical(&set);
+        assert_eq!(v, vec![vec![0], vec![1], vec![0, 1, 2]]);
+    }
+    #[test]
+    fn test_intersect() {
+        assert_eq!(intersect(&[1, 3, 5, 7], &[2, 3, 4, 7, 9]), vec![3, 7]);
+        assert_eq!(intersect(&[1, 2], &[]), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,215 @@
+use ahash::AHashMap;
+
+use crate::{
+    start::{Apriori, Write},
+    transaction_set::TransactionSet,
+};
+
+/// Mines the frequent itemsets with FP-Growth.
+/// Does not generate candidates, so it works well on dense datasets.
+pub struct FPGrowth<'a> {
+    data: &'a TransactionSet,
+    sup: u64,
+}
+
+impl<'a> FPGrowth<'a> {
+    pub fn new(data: &'a TransactionSet, sup: u64) -> Self {
+        Self { data, sup }
+    }
+}
+
+impl Apriori for FPGrowth<'_> {
+    fn run<T: Write>(self, out: &mut T) {
+        let tree = FPTree::new(
+            self.data.transactions.iter().map(|t| (t.as_slice(), 1)),
+            self.sup,
+        );
+        let mut prefix = Vec::new();
+        tree.mine(self.sup, &mut prefix, out);
+    }
+}
+
+/// A node of the FP-Tree.
+#[derive(Debug)]
+struct FPNode {
+    item: usize,
+    count: u64,
+    parent: usize,
+    children: AHashMap<usize, usize>,
+}
+
+impl FPNode {
+    fn new(item: usize, parent: usize) -> Self {
+        Self {
+            item,
+            count: 0,
+            parent,
+            children: AHashMap::new(),
+        }
+    }
+}
+
+/// A Frequent Pattern Tree.
+/// The nodes are stored in an arena with the root at index 0.
+#[derive(Debug)]
+pub struct FPTree {
+    nodes: Vec<FPNode>,
+    /// Maps each frequent item to every node that holds it
+    header: AHashMap<usize, Vec<usize>>,
+    /// The frequent items from the most to the least frequent
+    order: Vec<usize>,
+}
+
+impl FPTree {
+    /// Builds the tree from weighted transactions.
+    /// Items below sup are left out of the tree.
+    pub fn new<'a>(
+        transactions: impl Iterator<Item = (&'a [usize], u64)> + Clone,
+        sup: u64,
+    ) -> Self {
+        let mut supports: AHashMap<usize, u64> = AHashMap::new();
+        for (t, c) in transactions.clone() {
+            for &n in t {
+                *supports.entry(n).or_insert(0) += c;
+            }
+        }
+        let mut order: Vec<usize> = supports
+            .iter()
+            .filter(|(_, c)| **c >= sup)
+            .map(|(&n, _)| n)
+            .collect();
+        // Most frequent first, with ties broken by the item id
+        order.sort_by(|a, b| supports[b].cmp(&supports[a]).then(a.cmp(b)));
+        Self::with_order(transactions, order)
+    }
+    /// Builds the tree from weighted transactions with the items ordered like order,
+    /// instead of by their supports in transactions.
+    /// Items that are not in order are left out of the tree.
+    pub fn with_order<'a>(
+        transactions: impl Iterator<Item = (&'a [usize], u64)>,
+        order: Vec<usize>,
+    ) -> Self {
+        let rank: AHashMap<usize, usize> = order.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let mut tree = Self {
+            nodes: vec![FPNode::new(usize::MAX, usize::MAX)],
+            header: AHashMap::new(),
+            order,
+        };
+        let mut sorted = Vec::new();
+        for (t, c) in transactions {
+            sorted.clear();
+            sorted.extend(t.iter().filter(|n| rank.contains_key(n)));
+            sorted.sort_by_key(|n| rank[n]);
+            tree.insert(&sorted, c);
+        }
+        tree
+    }
+    /// Inserts the already sorted transaction v with count c
+    fn insert(&mut self, v: &[usize], c: u64) {
+        let mut curr = 0;
+        for &n in v {
+            curr = match self.nodes[curr].children.get(&n) {
+                Some(&child) => child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(FPNode::new(n, curr));
+                    self.nodes[curr].children.insert(n, child);
+                    self.header.entry(n).or_default().push(child);
+                    child
+                }
+            };
+            self.nodes[curr].count += c;
+        }
+    }
+    /// The support of a frequent item in the tree
+    pub fn support(&self, item: usize) -> u64 {
+        self.header
+            .get(&item)
+            .map(|nodes| nodes.iter().map(|&i| self.nodes[i].count).sum())
+            .unwrap_or(0)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+    /// The conditional pattern base of item as (path, count) pairs.
+    /// Paths go from the root down to the parent of the item.
+    fn conditional_base(&self, item: usize) -> Vec<(Vec<usize>, u64)> {
+        let mut base = Vec::new();
+        for &i in &self.header[&item] {
+            let mut path = Vec::new();
+            let mut curr = self.nodes[i].parent;
+            while curr != 0 {
+                path.push(self.nodes[curr].item);
+                curr = self.nodes[curr].parent;
+            }
+            if path.is_empty() {
+                continue;
+            }
+            path.reverse();
+            base.push((path, self.nodes[i].count));
+        }
+        base
+    }
+    /// Writes every frequent itemset of the tree, extended by prefix
+    fn mine<T: Write>(&self, sup: u64, prefix: &mut Vec<usize>, out: &mut T) {
+        for &item in self.order.iter().rev() {
+            self.mine_item(item, sup, prefix, out);
+        }
+    }
+    /// Writes every frequent itemset of the tree whose last item in the order is kept by keep.
+    /// The kept items have to have their full support in the tree.
+    pub fn mine_items<T: Write>(&self, sup: u64, keep: impl Fn(usize) -> bool, out: &mut T) {
+        let mut prefix = Vec::new();
+        for &item in self.order.iter().rev() {
+            if keep(item) && self.header.contains_key(&item) {
+                self.mine_item(item, sup, &mut prefix, out);
+            }
+        }
+    }
+    /// Writes prefix extended by item, then mines the conditional tree of item
+    fn mine_item<T: Write>(&self, item: usize, sup: u64, prefix: &mut Vec<usize>, out: &mut T) {
+        prefix.push(item);
+        let mut sorted = prefix.clone();
+        sorted.sort_unstable();
+        out.write_set_with_support(&sorted, self.support(item));
+        let base = self.conditional_base(item);
+        let tree = FPTree::new(base.iter().map(|(v, c)| (v.as_slice(), *c)), sup);
+        if !tree.is_empty() {
+            tree.mine(sup, prefix, out);
+        }
+        prefix.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::start::FrequentWriter;
+
+    use super::FPTree;
+
+    #[test]
+    fn test_tree() {
+        let data = [vec![1, 2, 3], vec![2, 3], vec![3, 4]];
+        let tree = FPTree::new(data.iter().map(|v| (v.as_slice(), 1)), 2);
+        assert_eq!(tree.order, vec![3, 2]);
+        assert_eq!(tree.support(3), 3);
+        assert_eq!(tree.support(2), 2);
+        assert_eq!(tree.support(1), 0);
+        // 3 -> 2 is shared by the first two transactions
+        assert_eq!(tree.nodes.len(), 3);
+        assert_eq!(tree.conditional_base(2), vec![(vec![3], 2)]);
+    }
+    #[test]
+    fn test_mine_items() {
+        let data = [vec![1, 2, 3], vec![1, 2, 3], vec![2, 4]];
+        let tree = FPTree::with_order(data.iter().map(|v| (v.as_slice(), 1)), vec![2, 1, 3]);
+        assert_eq!(tree.order, vec![2, 1, 3]);
+        let mut s = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        tree.mine_items(2, |item| item == 1, &mut s);
+        // Only the itemsets that have no item after 1 in the order
+        let s = s.into_inner();
+        assert_eq!(s, HashSet::from([vec![1], vec![1, 2]]));
+    }
+}
@@ -226,7 +226,7 @@ impl<const N: usize> AprioriHashTreeGeneric<N> {
         self.root.for_each(&mut f);
     }
     /// Gets an iterator for the Hash Tree
-    pub fn iter(&self) -> HashTreeIterator<N> {
+    pub fn iter(&self) -> HashTreeIterator<'_, N> {
         HashTreeIterator::new(self)
     }
     /// Gets the number of elements in the tree
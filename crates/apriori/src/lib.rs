@@ -1,9 +1,17 @@
 pub mod alone;
 pub mod apriori;
 pub mod array2d;
+pub mod checkpoint;
+pub mod closed;
 pub mod count;
+pub mod declat;
+pub mod eclat;
+pub mod fp_growth;
+pub mod hash_tree;
+pub mod maximal;
+pub mod rules;
 pub mod start;
 pub mod storage;
+pub mod threaded;
 pub mod transaction_set;
 pub mod trie;
-pub mod hash_tree;
@@ -0,0 +1,193 @@
+use ahash::AHashSet;
+
+use crate::{
+    eclat::{TidList, intersect, vertical},
+    start::{Apriori, Write},
+    transaction_set::TransactionSet,
+};
+
+/// Whether every item of the sorted a is in the sorted b
+fn is_subset(a: &[usize], b: &[usize]) -> bool {
+    let mut iter = b.iter();
+    a.iter().all(|n| iter.any(|m| m == n))
+}
+
+/// Collects the output of any runner so only the maximal itemsets get written.
+/// An itemset is maximal when none of its supersets are frequent,
+/// so it is enough to check the supersets with one more item.
+#[derive(Default)]
+pub struct MaximalWriter {
+    sets: Vec<(Vec<usize>, u64)>,
+}
+
+impl MaximalWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Writes the maximal itemsets to out
+    pub fn finish(self, out: &mut impl Write) {
+        let mut not_maximal = AHashSet::new();
+        for (v, _) in &self.sets {
+            for i in 0..v.len() {
+                let mut subset = v.clone();
+                subset.remove(i);
+                not_maximal.insert(subset);
+            }
+        }
+        for (v, sup) in &self.sets {
+            if not_maximal.contains(v) {
+                continue;
+            }
+            out.write_set_with_support(v, *sup);
+        }
+    }
+}
+
+impl Write for MaximalWriter {
+    fn write_set_with_support(&mut self, v: &[usize], sup: u64) {
+        self.sets.push((v.to_vec(), sup));
+    }
+}
+
+/// Mines the maximal frequent itemsets depth-first over tid lists.
+/// Like MaxMiner, a node whose head and tail together are frequent
+/// is written at once without exploring its subtree,
+/// and a node whose head and tail are already covered by a maximal itemset is skipped.
+pub struct MaximalRunner<'a> {
+    data: &'a TransactionSet,
+    sup: u64,
+}
+
+impl<'a> MaximalRunner<'a> {
+    pub fn new(data: &'a TransactionSet, sup: u64) -> Self {
+        Self { data, sup }
+    }
+    /// Adds v with support sup to the maximal itemsets found so far unless one of them covers it
+    fn add(found: &mut Vec<(Vec<usize>, u64)>, mut v: Vec<usize>, sup: u64) {
+        v.sort_unstable();
+        if found.iter().any(|(m, _)| is_subset(&v, m)) {
+            return;
+        }
+        found.retain(|(m, _)| !is_subset(m, &v));
+        found.push((v, sup));
+    }
+    /// Mines the maximal itemsets under head, which has support head_sup.
+    /// Each element of tail is (item, tid list of head + item) and is frequent.
+    fn mine(
+        &self,
+        head: &mut Vec<usize>,
+        head_sup: u64,
+        tail: &[(usize, TidList)],
+        found: &mut Vec<(Vec<usize>, u64)>,
+    ) {
+        if tail.is_empty() {
+            if !head.is_empty() {
+                Self::add(found, head.clone(), head_sup);
+            }
+            return;
+        }
+        let mut all: Vec<usize> = head
+            .iter()
+            .chain(tail.iter().map(|(n, _)| n))
+            .copied()
+            .collect();
+        all.sort_unstable();
+        if found.iter().any(|(m, _)| is_subset(&all, m)) {
+            return;
+        }
+        // Lookahead: the whole subtree collapses into head + tail if it is frequent
+        let mut tids = tail[0].1.clone();
+        for (_, t) in tail.iter().skip(1) {
+            if (tids.len() as u64) < self.sup {
+                break;
+            }
+            tids = intersect(&tids, t);
+        }
+        if tids.len() as u64 >= self.sup {
+            Self::add(found, all, tids.len() as u64);
+            return;
+        }
+        for (i, (a, tids_a)) in tail.iter().enumerate() {
+            let mut next = Vec::new();
+            for (b, tids_b) in tail.iter().skip(i + 1) {
+                let tids = intersect(tids_a, tids_b);
+                if tids.len() as u64 >= self.sup {
+                    next.push((*b, tids));
+                }
+            }
+            head.push(*a);
+            self.mine(head, tids_a.len() as u64, &next, found);
+            head.pop();
+        }
+    }
+}
+
+impl Apriori for MaximalRunner<'_> {
+    fn run<T: Write>(self, out: &mut T) {
+        let mut tail: Vec<_> = vertical(self.data)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, tids)| tids.len() as u64 >= self.sup)
+            .collect();
+        // Least frequent items first, so the frequent ones stay in the tails
+        tail.sort_by_key(|(n, tids)| (tids.len(), *n));
+        let mut found = Vec::new();
+        self.mine(&mut Vec::new(), self.data.len() as u64, &tail, &mut found);
+        for (v, sup) in &found {
+            out.write_set_with_support(v, *sup);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        apriori::AprioriRunner,
+        start::{Apriori, FrequentWriter},
+        transaction_set::TransactionSet,
+    };
+
+    use super::{MaximalRunner, MaximalWriter, is_subset};
+
+    fn maximal(v: &[&[usize]]) -> HashSet<Vec<usize>> {
+        v.iter().map(|v| v.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_is_subset() {
+        assert!(is_subset(&[1, 3], &[1, 2, 3]));
+        assert!(is_subset(&[], &[1]));
+        assert!(!is_subset(&[1, 4], &[1, 2, 3]));
+        assert!(!is_subset(&[3, 1], &[1, 2, 3]));
+    }
+    #[test]
+    fn test_maximal_writer() {
+        let set = TransactionSet::new(vec![vec![1, 2, 3], vec![1, 2, 3], vec![2, 4]], 5);
+        let mut writer = MaximalWriter::new();
+        AprioriRunner::new(&set, 2).run(&mut writer);
+        let mut s = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        writer.finish(&mut s);
+        assert_eq!(s.into_inner(), maximal(&[&[1, 2, 3]]));
+    }
+    #[test]
+    fn test_run_maximal() {
+        let set = TransactionSet::new(
+            vec![
+                vec![0, 1, 2],
+                vec![0, 1],
+                vec![1, 2],
+                vec![0, 1, 2],
+                vec![3],
+            ],
+            4,
+        );
+        let mut s = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        MaximalRunner::new(&set, 2).run(&mut s);
+        assert_eq!(s.into_inner(), maximal(&[&[0, 1, 2]]));
+        let mut s = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        MaximalRunner::new(&set, 3).run(&mut s);
+        assert_eq!(s.into_inner(), maximal(&[&[0, 1], &[1, 2]]));
+    }
+}
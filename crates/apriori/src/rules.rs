@@ -0,0 +1,405 @@
+use std::collections::HashSet;
+
+use crate::{
+    declat::difference,
+    start::Write,
+    storage::{AprioriCounter, AprioriFrequent},
+    trie::TrieCounter,
+};
+
+/// An association rule antecedent => consequent.
+/// support is the support of antecedent + consequent,
+/// and transactions is the size of the database it was mined from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub antecedent: Vec<usize>,
+    pub consequent: Vec<usize>,
+    pub support: u64,
+    pub antecedent_support: u64,
+    pub consequent_support: u64,
+    pub transactions: u64,
+}
+
+impl Rule {
+    pub fn confidence(&self) -> f64 {
+        self.support as f64 / self.antecedent_support as f64
+    }
+    /// How many times more often the consequent occurs with the antecedent than on its own
+    pub fn lift(&self) -> f64 {
+        self.confidence() / self.relative(self.consequent_support)
+    }
+    /// The relative support minus what it would be if both sides were independent
+    pub fn leverage(&self) -> f64 {
+        self.relative(self.support)
+            - self.relative(self.antecedent_support) * self.relative(self.consequent_support)
+    }
+    /// Infinite for rules that always hold
+    pub fn conviction(&self) -> f64 {
+        let confidence = self.confidence();
+        if confidence >= 1.0 {
+            return f64::INFINITY;
+        }
+        (1.0 - self.relative(self.consequent_support)) / (1.0 - confidence)
+    }
+    pub fn all_confidence(&self) -> f64 {
+        self.support as f64 / self.antecedent_support.max(self.consequent_support) as f64
+    }
+    pub fn kulczynski(&self) -> f64 {
+        (self.confidence() + self.support as f64 / self.consequent_support as f64) / 2.0
+    }
+    pub fn cosine(&self) -> f64 {
+        self.support as f64
+            / ((self.antecedent_support as f64) * (self.consequent_support as f64)).sqrt()
+    }
+    pub fn jaccard(&self) -> f64 {
+        self.support as f64
+            / (self.antecedent_support + self.consequent_support - self.support) as f64
+    }
+    pub fn measure(&self, m: Measure) -> f64 {
+        match m {
+            Measure::Support => self.support as f64,
+            Measure::Confidence => self.confidence(),
+            Measure::Lift => self.lift(),
+            Measure::Leverage => self.leverage(),
+            Measure::Conviction => self.conviction(),
+            Measure::AllConfidence => self.all_confidence(),
+            Measure::Kulczynski => self.kulczynski(),
+            Measure::Cosine => self.cosine(),
+            Measure::Jaccard => self.jaccard(),
+        }
+    }
+    fn relative(&self, sup: u64) -> f64 {
+        sup as f64 / self.transactions as f64
+    }
+}
+
+/// The interestingness measures of a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    Support,
+    Confidence,
+    Lift,
+    Leverage,
+    Conviction,
+    AllConfidence,
+    Kulczynski,
+    Cosine,
+    Jaccard,
+}
+
+impl std::str::FromStr for Measure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "support" => Ok(Self::Support),
+            "confidence" => Ok(Self::Confidence),
+            "lift" => Ok(Self::Lift),
+            "leverage" => Ok(Self::Leverage),
+            "conviction" => Ok(Self::Conviction),
+            "all-confidence" => Ok(Self::AllConfidence),
+            "kulczynski" => Ok(Self::Kulczynski),
+            "cosine" => Ok(Self::Cosine),
+            "jaccard" => Ok(Self::Jaccard),
+            _ => Err(format!(
+                "{s} is not one of support, confidence, lift, leverage, conviction, \
+                 all-confidence, kulczynski, cosine, jaccard"
+            )),
+        }
+    }
+}
+
+pub trait WriteRule {
+    fn write_rule(&mut self, rule: &Rule);
+}
+impl<T: std::io::Write> WriteRule for T {
+    /// Writes in the SPMF format, `1 2 ==> 3 #SUP: 4 #CONF: 0.8 #LIFT: 1.2`
+    fn write_rule(&mut self, rule: &Rule) {
+        let mut s = String::new();
+        for &n in &rule.antecedent {
+            s += format!("{n} ").as_str();
+        }
+        s += "==>";
+        for &n in &rule.consequent {
+            s += format!(" {n}").as_str();
+        }
+        s += format!(
+            " #SUP: {} #CONF: {} #LIFT: {}\n",
+            rule.support,
+            rule.confidence(),
+            rule.lift()
+        )
+        .as_str();
+        let _ = self.write(s.as_bytes());
+    }
+}
+
+/// Writes every rule of the frequent itemsets in counter with at least min_conf confidence.
+/// counter must hold every frequent itemset with its support,
+/// counted over a database of size transactions.
+/// The consequents of each itemset grow level-wise like Apriori,
+/// since moving items to the consequent can only lower the confidence.
+pub fn generate_rules(
+    counter: &impl AprioriCounter,
+    min_conf: f64,
+    transactions: u64,
+    out: &mut impl WriteRule,
+) {
+    counter.for_each(|v, sup| {
+        if v.len() < 2 || sup == 0 {
+            return;
+        }
+        let mut consequents: Vec<Vec<usize>> = v.iter().map(|&n| vec![n]).collect();
+        while !consequents.is_empty() && consequents[0].len() < v.len() {
+            let mut kept = HashSet::new();
+            for consequent in consequents {
+                let antecedent = difference(v, &consequent);
+                let get = |v: &[usize]| {
+                    counter
+                        .get_count(v)
+                        .expect("Every subset of a frequent itemset is frequent")
+                };
+                let rule = Rule {
+                    antecedent_support: get(&antecedent),
+                    consequent_support: get(&consequent),
+                    antecedent,
+                    consequent,
+                    support: sup,
+                    transactions,
+                };
+                if rule.confidence() < min_conf {
+                    continue;
+                }
+                out.write_rule(&rule);
+                kept.insert(rule.consequent);
+            }
+            consequents = next_consequents(&kept);
+        }
+    });
+}
+
+/// Joins the consequents of one length into the candidates one item longer,
+/// pruning those with a subset that was not kept.
+fn next_consequents(kept: &HashSet<Vec<usize>>) -> Vec<Vec<usize>> {
+    let mut next = Vec::new();
+    kept.join_fn(|v| {
+        let mut subset = Vec::with_capacity(v.len() - 1);
+        for i in 0..v.len() {
+            subset.clear();
+            subset.extend_from_slice(&v[..i]);
+            subset.extend_from_slice(&v[(i + 1)..]);
+            if !kept.contains(&subset) {
+                return;
+            }
+        }
+        next.push(v.to_vec());
+    });
+    next
+}
+
+/// Collects the output of any runner so the rules get written instead of the itemsets.
+#[derive(Default)]
+pub struct RuleWriter {
+    counter: TrieCounter,
+}
+
+impl RuleWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Writes the rules with at least min_conf confidence to out.
+    /// transactions is the size of the mined database.
+    pub fn finish(self, min_conf: f64, transactions: u64, out: &mut impl WriteRule) {
+        generate_rules(&self.counter, min_conf, transactions, out);
+    }
+}
+
+impl Write for RuleWriter {
+    fn write_set_with_support(&mut self, v: &[usize], sup: u64) {
+        self.counter.add(v, sup);
+    }
+}
+
+/// Keeps the rules that reach every threshold,
+/// optionally sorted from the best to the worst of a measure and cut to the top k.
+#[derive(Default)]
+pub struct RuleFilter {
+    thresholds: Vec<(Measure, f64)>,
+    sort_by: Option<Measure>,
+    top_k: Option<usize>,
+    rules: Vec<Rule>,
+}
+
+impl RuleFilter {
+    pub fn new(
+        thresholds: Vec<(Measure, f64)>,
+        sort_by: Option<Measure>,
+        top_k: Option<usize>,
+    ) -> Self {
+        Self {
+            thresholds,
+            sort_by,
+            top_k,
+            rules: Vec::new(),
+        }
+    }
+    /// Writes the kept rules to out
+    pub fn finish(mut self, out: &mut impl WriteRule) {
+        if let Some(m) = self.sort_by {
+            self.rules
+                .sort_by(|a, b| b.measure(m).total_cmp(&a.measure(m)));
+        }
+        if let Some(k) = self.top_k {
+            self.rules.truncate(k);
+        }
+        for rule in &self.rules {
+            out.write_rule(rule);
+        }
+    }
+}
+
+impl WriteRule for RuleFilter {
+    fn write_rule(&mut self, rule: &Rule) {
+        if self
+            .thresholds
+            .iter()
+            .all(|&(m, min)| rule.measure(m) >= min)
+        {
+            self.rules.push(rule.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apriori::AprioriRunner, start::Apriori, transaction_set::TransactionSet, trie::TrieCounter,
+    };
+
+    use super::{Measure, Rule, RuleFilter, RuleWriter, WriteRule, generate_rules};
+
+    #[derive(Default)]
+    struct Rules(Vec<Rule>);
+    impl WriteRule for Rules {
+        fn write_rule(&mut self, rule: &Rule) {
+            self.0.push(rule.clone());
+        }
+    }
+
+    fn rules(rules: Rules) -> Vec<(Vec<usize>, Vec<usize>)> {
+        let mut v: Vec<_> = rules
+            .0
+            .into_iter()
+            .map(|r| (r.antecedent, r.consequent))
+            .collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_generate_rules() {
+        let mut counter = TrieCounter::new();
+        counter.add(&[1], 4);
+        counter.add(&[2], 3);
+        counter.add(&[3], 2);
+        counter.add(&[1, 2], 3);
+        counter.add(&[1, 3], 2);
+        counter.add(&[2, 3], 2);
+        counter.add(&[1, 2, 3], 2);
+        let mut v = Rules::default();
+        generate_rules(&counter, 1.0, 4, &mut v);
+        assert!(v.0.iter().all(|r| r.confidence() == 1.0));
+        assert_eq!(
+            rules(v),
+            vec![
+                (vec![1, 3], vec![2]),
+                (vec![2], vec![1]),
+                (vec![2, 3], vec![1]),
+                (vec![3], vec![1]),
+                (vec![3], vec![1, 2]),
+                (vec![3], vec![2]),
+            ]
+        );
+        let mut v = Rules::default();
+        generate_rules(&counter, 0.0, 4, &mut v);
+        // Every non-empty proper subset of each itemset is a consequent
+        assert_eq!(v.0.len(), 3 * 2 + 6);
+    }
+    #[test]
+    fn test_rule_writer() {
+        let set = TransactionSet::new(vec![vec![1, 2, 3], vec![1, 2, 3], vec![2, 4]], 5);
+        let mut writer = RuleWriter::new();
+        AprioriRunner::new(&set, 2).run(&mut writer);
+        let mut v = Rules::default();
+        writer.finish(0.9, set.len() as u64, &mut v);
+        let v = rules(v);
+        assert!(v.contains(&(vec![1], vec![2, 3])));
+        assert!(v.contains(&(vec![1, 3], vec![2])));
+        assert!(!v.contains(&(vec![2], vec![1])));
+        let mut out = Vec::new();
+        out.write_rule(&Rule {
+            antecedent: vec![1, 3],
+            consequent: vec![2],
+            support: 2,
+            antecedent_support: 2,
+            consequent_support: 3,
+            transactions: 3,
+        });
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1 3 ==> 2 #SUP: 2 #CONF: 1 #LIFT: 1\n"
+        );
+    }
+    #[test]
+    fn test_measures() {
+        let rule = Rule {
+            antecedent: vec![1],
+            consequent: vec![2],
+            support: 2,
+            antecedent_support: 4,
+            consequent_support: 5,
+            transactions: 10,
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(close(rule.confidence(), 0.5));
+        assert!(close(rule.lift(), 1.0));
+        assert!(close(rule.leverage(), 0.0));
+        assert!(close(rule.conviction(), 1.0));
+        assert!(close(rule.all_confidence(), 0.4));
+        assert!(close(rule.kulczynski(), 0.45));
+        assert!(close(rule.cosine(), 2.0 / 20f64.sqrt()));
+        assert!(close(rule.jaccard(), 2.0 / 7.0));
+        let always = Rule {
+            antecedent_support: 2,
+            ..rule
+        };
+        assert_eq!(always.conviction(), f64::INFINITY);
+        assert_eq!("all-confidence".parse(), Ok(Measure::AllConfidence));
+        assert!("confidence2".parse::<Measure>().is_err());
+    }
+    #[test]
+    fn test_rule_filter() {
+        let mut counter = TrieCounter::new();
+        counter.add(&[1], 4);
+        counter.add(&[2], 3);
+        counter.add(&[3], 2);
+        counter.add(&[1, 2], 3);
+        counter.add(&[1, 3], 2);
+        counter.add(&[2, 3], 2);
+        counter.add(&[1, 2, 3], 2);
+        let mut filter = RuleFilter::new(vec![(Measure::Lift, 1.2)], None, None);
+        generate_rules(&counter, 0.0, 5, &mut filter);
+        let mut v = Rules::default();
+        filter.finish(&mut v);
+        assert!(!v.0.is_empty());
+        assert!(v.0.iter().all(|r| r.lift() >= 1.2));
+        let mut filter = RuleFilter::new(Vec::new(), Some(Measure::Jaccard), Some(3));
+        generate_rules(&counter, 0.0, 5, &mut filter);
+        let mut v = Rules::default();
+        filter.finish(&mut v);
+        assert_eq!(v.0.len(), 3);
+        assert!(v.0.windows(2).all(|w| w[0].jaccard() >= w[1].jaccard()));
+        // 1 => 2 and 2 => 1 have the highest jaccard of 3 / 4
+        assert!(v.0.iter().take(2).all(|r| r.jaccard() == 0.75));
+    }
+}
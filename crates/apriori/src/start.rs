@@ -16,17 +16,25 @@ pub trait Apriori {
 }
 
 pub trait Write {
-    fn write_set(&mut self, v: &[usize]);
+    /// Writes v along with its absolute support.
+    /// Every runner reports the support, so writers like ClosedWriter can rely on it.
+    fn write_set_with_support(&mut self, v: &[usize], sup: u64);
+    /// Makes everything written so far durable, before a checkpoint records it
+    fn flush_sets(&mut self) {}
 }
 impl<T: std::io::Write> Write for T {
-    fn write_set(&mut self, v: &[usize]) {
+    /// Writes in the SPMF format, `1 2 3 #SUP: 4`
+    fn write_set_with_support(&mut self, v: &[usize], sup: u64) {
         let mut s = String::new();
         for &n in v {
             s += format!("{n} ").as_str();
         }
-        s += "\n";
+        s += format!("#SUP: {sup}\n").as_str();
         let _ = self.write(s.as_bytes());
     }
+    fn flush_sets(&mut self) {
+        let _ = self.flush();
+    }
 }
 
 pub struct FrequentWriter<T: AprioriFrequent> {
@@ -40,7 +48,7 @@ impl<T: AprioriFrequent> FrequentWriter<T> {
 }
 
 impl<T: AprioriFrequent> Write for FrequentWriter<T> {
-    fn write_set(&mut self, v: &[usize]) {
+    fn write_set_with_support(&mut self, v: &[usize], _: u64) {
         self.inner.insert(v);
     }
 }
@@ -58,3 +66,19 @@ impl<T: AprioriFrequent + Default> Default for FrequentWriter<T> {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Write;
+
+    #[test]
+    fn test_write_with_support() {
+        let mut out = Vec::new();
+        out.write_set_with_support(&[1, 2], 5);
+        out.write_set_with_support(&[1, 2, 3], 4);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1 2 #SUP: 5\n1 2 3 #SUP: 4\n"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use ahash::AHashMap;
 
+use crate::start::Write;
+
 pub trait AprioriCounter {
     fn increment(&mut self, v: &[usize]) -> bool;
     fn insert(&mut self, v: &[usize]);
@@ -24,6 +26,20 @@ pub trait AprioriCounter {
             }
         });
     }
+    /// Like to_frequent_new, but also writes the frequent elements with their supports
+    fn write_frequent<T>(&self, sup: u64, out: &mut impl Write) -> T
+    where
+        T: AprioriFrequent + Default,
+    {
+        let mut f = T::default();
+        self.for_each(|v, count| {
+            if count >= sup {
+                out.write_set_with_support(v, count);
+                f.insert(v);
+            }
+        });
+        f
+    }
     fn len(&self) -> usize;
     fn is_empty(&self) -> bool {
         self.len() == 0
@@ -0,0 +1,141 @@
+use std::{thread, time::Instant};
+
+use parallel::traits::Convertable;
+
+use crate::{
+    apriori::{apriori_pass_one_counter, apriori_pass_three_counter, apriori_pass_two_counter},
+    array2d::AprioriP2Counter,
+    start::{Apriori, Write},
+    storage::{AprioriCounter, AprioriFrequent},
+    transaction_set::TransactionSet,
+    trie::{TrieCounter, TrieSet},
+};
+
+/// The number of threads to use when 0 is asked for
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Splits data into at most threads partitions of about the same number of transactions,
+/// without copying them
+pub fn split(data: &[Vec<usize>], threads: usize) -> Vec<&[Vec<usize>]> {
+    let chunk = data.len().div_ceil(threads.max(1)).max(1);
+    data.chunks(chunk).collect()
+}
+
+/// Counts every partition on its own thread into a counter made by make,
+/// then adds all the counters into the first one.
+/// A single partition gets counted on the calling thread.
+/// The counters of make have to share the same layout.
+pub fn count_parallel<C: Convertable + Send>(
+    parts: &[&[Vec<usize>]],
+    make: impl Fn() -> C + Sync,
+    count: impl Fn(&[Vec<usize>], &mut C) + Sync,
+) -> C {
+    if let [part] = parts {
+        let mut counter = make();
+        count(part, &mut counter);
+        return counter;
+    }
+    let mut counters = thread::scope(|s| {
+        let handles: Vec<_> = parts
+            .iter()
+            .map(|part| {
+                let (make, count) = (&make, &count);
+                s.spawn(move || {
+                    let mut counter = make();
+                    count(part, &mut counter);
+                    counter
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    })
+    .into_iter();
+    let mut merged = counters.next().unwrap_or_else(make);
+    for mut counter in counters {
+        merged.add_from_vec(&counter.to_vec());
+    }
+    merged
+}
+
+/// Count Distribution over threads instead of MPI ranks.
+/// Each thread counts the candidates in its own partition,
+/// and the counters are merged before finding the frequent itemsets.
+pub struct ThreadedCountDistribution<'a> {
+    data: &'a TransactionSet,
+    sup: u64,
+    threads: usize,
+}
+
+impl<'a> ThreadedCountDistribution<'a> {
+    /// Uses every available core if threads is 0
+    pub fn new(data: &'a TransactionSet, sup: u64, threads: usize) -> Self {
+        let threads = if threads == 0 {
+            default_threads()
+        } else {
+            threads
+        };
+        Self { data, sup, threads }
+    }
+}
+
+impl Apriori for ThreadedCountDistribution<'_> {
+    fn run<T: Write>(self, out: &mut T) {
+        let parts = split(self.data, self.threads);
+        let counts = count_parallel(
+            &parts,
+            || vec![0u64; self.data.num_items],
+            apriori_pass_one_counter,
+        );
+        let mut p1 = Vec::new();
+        for (i, &count) in counts.iter().enumerate() {
+            if count >= self.sup {
+                out.write_set_with_support(&[i], count);
+                p1.push(i);
+            }
+        }
+        if p1.is_empty() {
+            return;
+        }
+        let counter = count_parallel(
+            &parts,
+            || AprioriP2Counter::new(&p1),
+            apriori_pass_two_counter,
+        );
+        let mut prev: TrieSet = counter.write_frequent(self.sup, out);
+        for i in 3.. {
+            if prev.is_empty() {
+                break;
+            }
+            let prev_time = Instant::now();
+            let counter = count_parallel(
+                &parts,
+                || prev.join_new::<TrieCounter>(),
+                |part, counter| apriori_pass_three_counter(part, counter, i),
+            );
+            prev = counter.write_frequent(self.sup, out);
+            println!("{i} {:?}", prev_time.elapsed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transaction_set::TransactionSet;
+
+    use super::split;
+
+    #[test]
+    fn test_split() {
+        let set = TransactionSet::new(vec![vec![1], vec![2], vec![3], vec![1, 2], vec![2, 3]], 4);
+        let parts = split(&set, 2);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0], &set[..3]);
+        assert_eq!(parts[1], &set[3..]);
+        assert_eq!(split(&set, 8).len(), 5);
+    }
+}
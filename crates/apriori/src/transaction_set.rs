@@ -1,12 +1,108 @@
-use std::{fs::File, io::{BufRead, BufReader}, ops::{Deref, DerefMut}};
+use std::{
+    fmt::Display,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    ops::{Deref, DerefMut, Range},
+};
+
+/// An error while reading a .dat file
+#[derive(Debug)]
+pub enum DatError {
+    IO(std::io::Error),
+    /// token on the 1-indexed line is not an item id
+    InvalidToken {
+        line: usize,
+        token: String,
+    },
+    /// token on the line starting at the byte offset is not an item id,
+    /// for a range of a file where the line number is unknown
+    InvalidTokenAt {
+        offset: u64,
+        token: String,
+    },
+    /// Another rank could not load its part of the file
+    OtherRank,
+}
+impl Display for DatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatError::IO(e) => write!(f, "{e}"),
+            DatError::InvalidToken { line, token } => {
+                write!(f, "line {line}: {token:?} is not an item id")
+            }
+            DatError::InvalidTokenAt { offset, token } => {
+                write!(f, "line at byte {offset}: {token:?} is not an item id")
+            }
+            DatError::OtherRank => write!(f, "another rank could not load its part of the file"),
+        }
+    }
+}
+impl std::error::Error for DatError {}
+
+/// What to do with a line that has an invalid token
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BadLines {
+    /// Stop with the error
+    #[default]
+    Fail,
+    /// Leave the line out of the database
+    Skip,
+}
+
+/// A minimum support, either as a transaction count
+/// or as a fraction of the database that still has to be resolved against its size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Support {
+    Count(u64),
+    Relative(f64),
+}
+impl Support {
+    /// The support count for a database of the given number of transactions
+    pub fn resolve(self, transactions: u64) -> u64 {
+        match self {
+            Support::Count(c) => c,
+            Support::Relative(f) => {
+                let c = f * transactions as f64;
+                // 2% of 150 should be 3 even if the product is 3.0000000000000004
+                if (c - c.round()).abs() < 1e-9 {
+                    c.round() as u64
+                } else {
+                    c.ceil() as u64
+                }
+            }
+        }
+    }
+}
+impl From<u64> for Support {
+    fn from(value: u64) -> Self {
+        Support::Count(value)
+    }
+}
+/// Parses a count like `20`, a fraction like `0.02` or a percentage like `2%`
+impl std::str::FromStr for Support {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let relative = if let Some(p) = s.strip_suffix('%') {
+            p.parse::<f64>().map_err(|e| format!("{e}"))? / 100.0
+        } else if let Ok(c) = s.parse::<u64>() {
+            return Ok(Support::Count(c));
+        } else {
+            s.parse::<f64>().map_err(|e| format!("{e}"))?
+        };
+        if !(relative > 0.0 && relative <= 1.0) {
+            return Err(format!("{s} is not a fraction in (0, 1]"));
+        }
+        Ok(Support::Relative(relative))
+    }
+}
 
 /// A 0-indexed item set
 /// A Transactional Database
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct TransactionSet {
     pub transactions: Vec<Vec<usize>>,
     pub num_items: usize,
-    pub size: usize
+    pub size: usize,
 }
 // Dereferences to the underlying Vector
 impl Deref for TransactionSet {
@@ -25,8 +121,16 @@ impl DerefMut for TransactionSet {
 impl TransactionSet {
     /// Constructor
     pub fn new(transactions: Vec<Vec<usize>>, num_items: usize) -> Self {
-        let mut size = transactions.iter().map(|v| v.len()).sum();
-        Self { transactions, num_items, size }
+        let size = transactions.iter().map(|v| v.len()).sum();
+        Self {
+            transactions,
+            num_items,
+            size,
+        }
+    }
+    /// The support count of sup in this database
+    pub fn support_count(&self, sup: Support) -> u64 {
+        sup.resolve(self.len() as u64)
     }
     /// Iterates over all the transactions
     pub fn iter(&self) -> impl Iterator<Item = &Vec<usize>> {
@@ -35,23 +139,173 @@ impl TransactionSet {
     /// Constructs the set from a .dat file
     /// .dat file is a file with one transaction per line.
     /// Each transaction is a space-separated list of ids.
-    pub fn from_dat(f: File) -> Self {
-        let mut max = 0;
-        let mut transactions = Vec::new();
+    /// Fails on the first invalid token.
+    pub fn from_dat(f: impl Read) -> Result<Self, DatError> {
+        Self::from_dat_with(f, BadLines::Fail).map(|(set, _)| set)
+    }
+    /// Like from_dat, but bad_lines decides what happens to lines with an invalid token.
+    /// Also returns how many lines were skipped.
+    pub fn from_dat_with(f: impl Read, bad_lines: BadLines) -> Result<(Self, usize), DatError> {
+        let mut parser = DatParser::new(bad_lines);
         // Loops through each line of the file
-        for l in BufReader::new(f).lines() {
-            if l.is_err() {
-                continue;
+        for (i, l) in BufReader::new(f).lines().enumerate() {
+            parser
+                .parse(&l.map_err(DatError::IO)?)
+                .map_err(|token| DatError::InvalidToken { line: i + 1, token })?;
+        }
+        Ok(parser.finish())
+    }
+    /// Like from_dat_with, but only reads the lines that start inside the byte range,
+    /// so a file split into consecutive ranges has each line in exactly one of them.
+    /// The errors give the byte offset of the line in the file,
+    /// and num_items only covers the items of the range.
+    pub fn from_dat_range(
+        f: impl Read + Seek,
+        range: Range<u64>,
+        bad_lines: BadLines,
+    ) -> Result<(Self, usize), DatError> {
+        let mut f = BufReader::new(f);
+        let mut buf = Vec::new();
+        let mut pos = range.start;
+        if range.start > 0 {
+            // The line containing the byte before the range belongs to the previous range
+            f.seek(SeekFrom::Start(range.start - 1))
+                .map_err(DatError::IO)?;
+            pos = range.start - 1 + f.read_until(b'\n', &mut buf).map_err(DatError::IO)? as u64;
+        }
+        let mut parser = DatParser::new(bad_lines);
+        while pos < range.end {
+            buf.clear();
+            let read = f.read_until(b'\n', &mut buf).map_err(DatError::IO)?;
+            if read == 0 {
+                break;
+            }
+            let offset = pos;
+            pos += read as u64;
+            let line = std::str::from_utf8(&buf).map_err(|e| {
+                DatError::IO(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            })?;
+            let line = line.strip_suffix('\n').unwrap_or(line);
+            parser
+                .parse(line.strip_suffix('\r').unwrap_or(line))
+                .map_err(|token| DatError::InvalidTokenAt { offset, token })?;
+        }
+        Ok(parser.finish())
+    }
+}
+
+/// Builds a TransactionSet from the lines of a .dat file
+struct DatParser {
+    bad_lines: BadLines,
+    max: usize,
+    skipped: usize,
+    transactions: Vec<Vec<usize>>,
+}
+impl DatParser {
+    fn new(bad_lines: BadLines) -> Self {
+        Self {
+            bad_lines,
+            max: 0,
+            skipped: 0,
+            transactions: Vec::new(),
+        }
+    }
+    /// Adds the transaction on line, or fails with its invalid token
+    fn parse(&mut self, line: &str) -> Result<(), String> {
+        // Parses the transaction
+        let items: Result<Vec<usize>, _> = line
+            .split_whitespace()
+            .map(|s| s.parse::<usize>().map_err(|_| s))
+            .collect();
+        let mut items = match (items, self.bad_lines) {
+            (Ok(items), _) => items,
+            (Err(_), BadLines::Skip) => {
+                self.skipped += 1;
+                return Ok(());
+            }
+            (Err(token), BadLines::Fail) => return Err(token.to_string()),
+        };
+        // Sorts the items and sets the max
+        items.sort();
+        items.dedup();
+        if let Some(&last) = items.last() {
+            self.max = (last + 1).max(self.max);
+        }
+        self.transactions.push(items);
+        Ok(())
+    }
+    /// The set and the number of skipped lines
+    fn finish(self) -> (TransactionSet, usize) {
+        (
+            TransactionSet::new(self.transactions, self.max),
+            self.skipped,
+        )
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{BadLines, DatError, Support, TransactionSet};
+
+    #[test]
+    fn test_support() {
+        assert_eq!("20".parse(), Ok(Support::Count(20)));
+        assert_eq!("0.02".parse(), Ok(Support::Relative(0.02)));
+        assert_eq!("2%".parse(), Ok(Support::Relative(0.02)));
+        assert!("0".parse::<Support>().is_ok());
+        assert!("0.0".parse::<Support>().is_err());
+        assert!("150%".parse::<Support>().is_err());
+        assert!("a".parse::<Support>().is_err());
+        let set = TransactionSet::new(vec![vec![0]; 150], 1);
+        assert_eq!(set.support_count(Support::Count(7)), 7);
+        assert_eq!(set.support_count(Support::Relative(0.02)), 3);
+        assert_eq!(set.support_count(Support::Relative(0.015)), 3);
+    }
+    #[test]
+    fn test_from_dat() {
+        let set = TransactionSet::from_dat("3 1 2\n\n2 2 0\n".as_bytes()).unwrap();
+        assert_eq!(set.transactions, vec![vec![1, 2, 3], vec![], vec![0, 2]]);
+        assert_eq!(set.num_items, 4);
+        match TransactionSet::from_dat("1 2\n3 x 4\n".as_bytes()) {
+            Err(DatError::InvalidToken { line, token }) => {
+                assert_eq!(line, 2);
+                assert_eq!(token, "x");
             }
-            let line = l.unwrap();
-            // Parses the transaction
-            let mut items: Vec<usize> = line.split_whitespace().map(|s| s.parse::<usize>().unwrap()).collect();
-            // Sorts the items and sets the max
-            items.sort();
-            items.dedup();
-            max = (*items.iter().max().unwrap()).max(max);
-            transactions.push(items);
-        }
-        Self::new(transactions, max + 1)
-    }
-}
\ No newline at end of file
+            _ => panic!("Expected an invalid token"),
+        }
+        let (set, skipped) =
+            TransactionSet::from_dat_with("1 2\n3 x 4\n-1\n5\n".as_bytes(), BadLines::Skip)
+                .unwrap();
+        assert_eq!(set.transactions, vec![vec![1, 2], vec![5]]);
+        assert_eq!(skipped, 2);
+    }
+    #[test]
+    fn test_from_dat_range() {
+        let file = "1 2\n\n3 4 5\r\n6\n7 8";
+        let read = |start, end| {
+            let (set, _) =
+                TransactionSet::from_dat_range(Cursor::new(file), start..end, BadLines::Fail)
+                    .unwrap();
+            set.transactions
+        };
+        let whole = TransactionSet::from_dat(file.as_bytes())
+            .unwrap()
+            .transactions;
+        assert_eq!(read(0, file.len() as u64), whole);
+        // Every way of splitting the file in two reads each line once
+        for mid in 0..=file.len() as u64 {
+            let mut v = read(0, mid);
+            v.extend(read(mid, file.len() as u64));
+            assert_eq!(v, whole);
+        }
+        assert_eq!(read(4, 5), vec![vec![]]);
+        assert_eq!(read(5, 6), vec![vec![3, 4, 5]]);
+        assert_eq!(read(6, 14), vec![vec![6]]);
+        assert!(read(30, 40).is_empty());
+        match TransactionSet::from_dat_range(Cursor::new("1\n2\nx\n"), 2..6, BadLines::Fail) {
+            Err(DatError::InvalidTokenAt { offset, .. }) => assert_eq!(offset, 4),
+            _ => panic!("Expected an invalid token"),
+        }
+    }
+}
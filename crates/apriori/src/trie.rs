@@ -34,6 +34,38 @@ impl TrieSet {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Reads the output of to_vec, or None if v is not one.
+    /// Unlike add_from_vec, it also counts the itemsets for len.
+    pub fn from_vec(v: &[u64]) -> Option<Self> {
+        let (&depth, rest) = v.split_first()?;
+        let mut set = Self::new();
+        // The empty set is only its depth of 0
+        if depth == 0 {
+            return rest.is_empty().then_some(set);
+        }
+        let mut iter = rest.iter().copied();
+        set.read(depth, &mut iter, &mut Vec::new())?;
+        iter.next().is_none().then_some(set)
+    }
+    fn read(
+        &mut self,
+        depth: u64,
+        v: &mut impl Iterator<Item = u64>,
+        stack: &mut Vec<usize>,
+    ) -> Option<()> {
+        if depth == 0 {
+            self.insert(stack);
+            return Some(());
+        }
+        // to_vec only writes the nodes that have children
+        let size = v.next().filter(|&size| size > 0)?;
+        for _ in 0..size {
+            stack.push(usize::try_from(v.next()?).ok()?);
+            self.read(depth - 1, v, stack)?;
+            stack.pop();
+        }
+        Some(())
+    }
 }
 impl AprioriFrequent for TrieSet {
     fn for_each(&self, mut f: impl FnMut(&[usize])) {
@@ -482,4 +514,50 @@ mod tests {
         assert_eq!(trie.get(&[1, 2, 4]), Some(true));
         assert_eq!(trie.get(&[1, 3, 4]), Some(true));
     }
+    #[test]
+    fn test_from_vec() {
+        let mut trie = TrieSet::new();
+        trie.insert(&[1, 2, 3]);
+        trie.insert(&[1, 2, 4]);
+        trie.insert(&[1, 3, 4]);
+        let v = trie.to_vec();
+        let read = TrieSet::from_vec(&v).unwrap();
+        assert_eq!(read.len(), 3);
+        assert!(read.contains(&[1, 2, 4]));
+        assert!(!read.contains(&[1, 2]));
+        assert!(
+            TrieSet::from_vec(&TrieSet::new().to_vec())
+                .unwrap()
+                .is_empty()
+        );
+        assert!(TrieSet::from_vec(&[]).is_none());
+        assert!(TrieSet::from_vec(&v[..v.len() - 1]).is_none());
+        assert!(TrieSet::from_vec(&[&v[..], &[7]].concat()).is_none());
+        assert!(TrieSet::from_vec(&[2, 1, 5, 0]).is_none());
+    }
+    #[test]
+    fn test_convertable_bytes() {
+        let mut trie = TrieCounter::new();
+        trie.add(&[1, 2, 3], 2);
+        trie.add(&[1, 2, 4], 300);
+        trie.add(&[1, 3, 4], 0);
+        let bytes = trie.to_bytes();
+        assert!(bytes.len() < trie.to_vec().len() * 8);
+        let mut other = TrieCounter::new();
+        other.add(&[1, 2, 3], 1);
+        other.add_from_bytes(&bytes);
+        assert_eq!(other.get_count(&[1, 2, 3]), Some(3));
+        assert_eq!(other.get_count(&[1, 2, 4]), Some(300));
+        assert_eq!(other.get_count(&[1, 3, 4]), None);
+
+        let mut trie = TrieSet::new();
+        trie.insert(&[1, 2]);
+        trie.insert(&[5, 1000]);
+        let bytes = trie.to_bytes();
+        let mut other = TrieSet::new();
+        other.add_from_bytes(&bytes);
+        assert!(other.contains(&[1, 2]));
+        assert!(other.contains(&[5, 1000]));
+        assert!(!other.contains(&[1, 1000]));
+    }
 }
@@ -1,11 +1,22 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, env, fs, process};
 
 use apriori::{
     alone::AprioriTrie,
     apriori::AprioriRunner,
+    checkpoint::Checkpoints,
+    closed::{ClosedRunner, ClosedWriter},
+    declat::DEclat,
+    eclat::Eclat,
+    fp_growth::FPGrowth,
+    maximal::{MaximalRunner, MaximalWriter},
+    rules::{Rule, RuleWriter, WriteRule},
     start::{Apriori, FrequentWriter},
+    threaded::ThreadedCountDistribution,
+    transaction_set::TransactionSet,
+};
+use tester::test_utils::{
+    Solved, SupportWriter, checkpoint_after, test_generic, test_generic_with_option,
 };
-use tester::test_utils::{Solved, test_generic};
 
 #[test]
 fn test_apriori() {
@@ -17,6 +28,23 @@ fn test_apriori() {
     });
 }
 #[test]
+fn test_apriori_resume() {
+    test_generic("../../test_files", |t, s| {
+        let mut full = SupportWriter::default();
+        AprioriRunner::new(&t, s).run(&mut full);
+        let dir = env::temp_dir().join(format!("apriori_resume_{}", process::id()));
+        let checkpoints = Checkpoints::new(&dir);
+        let mut out = checkpoint_after(&full.0, 3, s, &checkpoints);
+        let checkpoint = checkpoints.load().unwrap().unwrap();
+        AprioriRunner::new(&t, s)
+            .with_checkpoints(checkpoints)
+            .with_resume(checkpoint)
+            .run(&mut out);
+        fs::remove_dir_all(dir).unwrap();
+        Solved::from_reader(&out[..]).unwrap()
+    });
+}
+#[test]
 fn test_apriori_trie() {
     test_generic("../../test_files", |t, s| {
         let a = AprioriTrie::new(t, s);
@@ -25,3 +53,153 @@ fn test_apriori_trie() {
         Solved::new(writer.into_inner())
     });
 }
+#[test]
+fn test_fp_growth() {
+    test_generic("../../test_files", |t, s| {
+        let a = FPGrowth::new(&t, s);
+        let mut writer: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        a.run(&mut writer);
+        Solved::new(writer.into_inner())
+    });
+}
+#[test]
+fn test_eclat() {
+    test_generic("../../test_files", |t, s| {
+        let a = Eclat::new(&t, s);
+        let mut writer: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        a.run(&mut writer);
+        Solved::new(writer.into_inner())
+    });
+}
+#[test]
+fn test_declat() {
+    test_generic("../../test_files", |t, s| {
+        let a = DEclat::new(&t, s);
+        let mut writer: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        a.run(&mut writer);
+        Solved::new(writer.into_inner())
+    });
+}
+#[test]
+fn test_declat_dense() {
+    // Dense enough that the classes switch to diffsets
+    test_generic("../../test_files/dense", |t, s| {
+        let a = DEclat::new(&t, s);
+        let mut writer: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        a.run(&mut writer);
+        Solved::new(writer.into_inner())
+    });
+}
+#[test]
+fn test_threaded_count_distribution() {
+    test_generic("../../test_files", |t, s| {
+        let a = ThreadedCountDistribution::new(&t, s, 4);
+        let mut writer: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        a.run(&mut writer);
+        Solved::new(writer.into_inner())
+    });
+}
+#[test]
+fn test_closed() {
+    test_generic_with_option("../../test_files", |t, s| {
+        let mut closed: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        ClosedRunner::new(&t, s).run(&mut closed);
+        let mut writer = ClosedWriter::new();
+        AprioriRunner::new(&t, s).run(&mut writer);
+        let mut filtered: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        writer.finish(&mut filtered);
+        assert_eq!(closed.into_inner(), filtered.into_inner());
+        None
+    });
+}
+#[test]
+fn test_maximal() {
+    test_generic_with_option("../../test_files", |t, s| {
+        let mut maximal: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        MaximalRunner::new(&t, s).run(&mut maximal);
+        let maximal = maximal.into_inner();
+        let mut writer = MaximalWriter::new();
+        AprioriRunner::new(&t, s).run(&mut writer);
+        let mut filtered: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        writer.finish(&mut filtered);
+        assert_eq!(maximal, filtered.into_inner());
+        let mut writer = MaximalWriter::new();
+        FPGrowth::new(&t, s).run(&mut writer);
+        let mut filtered: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        writer.finish(&mut filtered);
+        assert_eq!(maximal, filtered.into_inner());
+        None
+    });
+}
+fn check_supports(t: &TransactionSet, writer: SupportWriter) {
+    assert!(!writer.0.is_empty());
+    for (v, sup) in writer.0 {
+        let count = t
+            .iter()
+            .filter(|d| v.iter().all(|n| d.binary_search(n).is_ok()))
+            .count();
+        assert_eq!(sup, count as u64, "{v:?}");
+    }
+}
+#[test]
+fn test_supports() {
+    test_generic_with_option("../../test_files", |t, s| {
+        let mut writer = SupportWriter::default();
+        AprioriRunner::new(&t, s).run(&mut writer);
+        check_supports(&t, writer);
+        let mut writer = SupportWriter::default();
+        FPGrowth::new(&t, s).run(&mut writer);
+        check_supports(&t, writer);
+        let mut writer = SupportWriter::default();
+        Eclat::new(&t, s).run(&mut writer);
+        check_supports(&t, writer);
+        let mut writer = SupportWriter::default();
+        DEclat::new(&t, s).run(&mut writer);
+        check_supports(&t, writer);
+        let mut writer = SupportWriter::default();
+        MaximalRunner::new(&t, s).run(&mut writer);
+        check_supports(&t, writer);
+        let mut writer = SupportWriter::default();
+        AprioriTrie::new(t.clone(), s).run(&mut writer);
+        check_supports(&t, writer);
+        None
+    });
+}
+#[derive(Default)]
+struct RuleSet(HashSet<(Vec<usize>, Vec<usize>)>);
+impl WriteRule for RuleSet {
+    fn write_rule(&mut self, rule: &Rule) {
+        assert!(rule.confidence() >= 0.8);
+        self.0
+            .insert((rule.antecedent.clone(), rule.consequent.clone()));
+    }
+}
+#[test]
+fn test_rules() {
+    test_generic_with_option("../../test_files", |t, s| {
+        let mut writer = RuleWriter::new();
+        FPGrowth::new(&t, s).run(&mut writer);
+        let mut rules = RuleSet::default();
+        writer.finish(0.8, t.len() as u64, &mut rules);
+        // Tries every split of every frequent itemset
+        let mut supports = SupportWriter::default();
+        Eclat::new(&t, s).run(&mut supports);
+        let mut expected = HashSet::new();
+        for (v, &sup) in &supports.0 {
+            for mask in 1..((1 << v.len()) - 1) {
+                let (consequent, antecedent): (Vec<_>, Vec<_>) = v
+                    .iter()
+                    .enumerate()
+                    .partition(|(i, _)| mask & (1 << i) != 0);
+                let consequent: Vec<usize> = consequent.into_iter().map(|(_, &n)| n).collect();
+                let antecedent: Vec<usize> = antecedent.into_iter().map(|(_, &n)| n).collect();
+                if sup as f64 / supports.0[&antecedent] as f64 >= 0.8 {
+                    expected.insert((antecedent, consequent));
+                }
+            }
+        }
+        assert!(!expected.is_empty());
+        assert_eq!(rules.0, expected);
+        None
+    });
+}
@@ -1,8 +1,9 @@
 use std::time::Instant;
 
 use apriori::{
-    apriori::{apriori_pass_one, apriori_pass_two_counter},
+    apriori::{apriori_pass_one_write, apriori_pass_two_counter},
     array2d::AprioriP2Counter,
+    checkpoint::{Checkpoint, Checkpoints, CountingWriter},
     start::Write,
     storage::{AprioriCounter, AprioriCounting, AprioriFrequent, Joinable},
     transaction_set::TransactionSet,
@@ -15,37 +16,81 @@ use crate::tid::{CandidateID, Candidates, TransformedDatabase};
 pub struct AprioriHybridRunner<'a> {
     data: &'a mut TransactionSet,
     sup: u64,
+    checkpoints: Option<Checkpoints>,
+    resume: Option<Checkpoint>,
 }
 
 impl<'a> AprioriHybridRunner<'a> {
     pub fn new(data: &'a mut TransactionSet, sup: u64) -> Self {
-        Self { data, sup }
+        Self {
+            data,
+            sup,
+            checkpoints: None,
+            resume: None,
+        }
+    }
+    /// Saves a checkpoint after every pass from 2 on
+    pub fn with_checkpoints(mut self, checkpoints: Checkpoints) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+    /// Continues after the pass of checkpoint, which has to be made with the same support.
+    /// The resumed run starts out counting with Apriori again.
+    pub fn with_resume(mut self, checkpoint: Checkpoint) -> Self {
+        self.resume = Some(checkpoint);
+        self
     }
     pub fn run<T: Write>(self, writer: &mut T) {
-        let p1 = apriori_pass_one(self.data, self.sup);
-        p1.iter().for_each(|&n| writer.write_set(&[n]));
-        let mut counter = AprioriP2Counter::new(&p1);
-        apriori_pass_two_counter(self.data, &mut counter);
+        let mut writer = CountingWriter::new(writer, self.resume.as_ref().map_or(0, |c| c.written));
         let mut trie = TrieCounter::new();
-        counter.for_each(|v, n| {
-            if n >= self.sup {
-                writer.write_set(v);
-                trie.add(v, n);
+        let start = match self.resume {
+            // The checkpoint of the last pass has nothing left to join
+            Some(checkpoint) if checkpoint.frequent.is_empty() => return,
+            Some(checkpoint) => {
+                checkpoint.frequent.for_each(|v| {
+                    trie.add(v, self.sup);
+                });
+                checkpoint.pass + 1
             }
-        });
+            None => {
+                let p1 = apriori_pass_one_write(self.data, self.sup, &mut writer);
+                let mut counter = AprioriP2Counter::new(&p1);
+                apriori_pass_two_counter(self.data, &mut counter);
+                let mut frequent = TrieSet::new();
+                counter.for_each(|v, n| {
+                    if n >= self.sup {
+                        writer.write_set_with_support(v, n);
+                        trie.add(v, n);
+                        frequent.insert(v);
+                    }
+                });
+                if let Some(c) = &self.checkpoints {
+                    c.save(2, self.sup, &mut frequent, &mut writer);
+                }
+                3
+            }
+        };
+        let checkpointing = self.checkpoints.is_some();
         let mut prev = AprioriHybridContainer::new(trie, self.sup);
-        for n in 3.. {
+        for n in start.. {
             let prev_time = Instant::now();
             prev.run(self.data, n);
             println!("{n} {:?}", prev_time.elapsed());
             let mut total = 0;
+            let mut frequent = TrieSet::new();
             prev.for_each(|v, c| {
                 if c < self.sup {
                     return;
                 }
                 total += 1;
-                writer.write_set(v);
+                writer.write_set_with_support(v, c);
+                if checkpointing {
+                    frequent.insert(v);
+                }
             });
+            if let Some(c) = &self.checkpoints {
+                c.save(n, self.sup, &mut frequent, &mut writer);
+            }
             if total == 0 {
                 break;
             }
@@ -95,15 +140,29 @@ impl AprioriHybridContainer {
             }
         }
     }
+    /// Whether the next pass should switch to AprioriTID,
+    /// which is once the candidates of the last pass occur fewer times than there are items in data
+    pub fn should_switch(&self, data: &TransactionSet) -> bool {
+        (self.prev_total as usize) < data.size
+    }
+    /// How many times the candidates of the last Apriori pass occurred, or u64::MAX before any
+    pub fn occurrences(&self) -> u64 {
+        self.prev_total
+    }
     pub fn run(&mut self, data: &mut TransactionSet, n: usize) {
+        let tid = self.should_switch(data);
+        self.run_switched(data, n, tid);
+    }
+    /// Like run, but switches to AprioriTID if tid instead of deciding from data,
+    /// so that containers over different partitions can switch together
+    pub fn run_switched(&mut self, data: &[Vec<usize>], n: usize, tid: bool) {
         match &mut self.container {
             HybridCandidates::Apriori(trie_set) => {
-                let prev = self.prev;
                 let trie: TrieSet = trie_set.to_frequent_new(self.sup);
                 let mut trie: TrieCounter = trie.join_new();
                 let mut total = 0;
                 self.prev = trie.len();
-                if (self.prev_total as usize) < data.size {
+                if tid {
                     println!("SWITCH");
                     let mut transition = AprioriTransition::new();
                     let mut candidates = Candidates::new(self.sup);
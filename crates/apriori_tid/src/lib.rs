@@ -1,2 +1,2 @@
+pub mod hybrid;
 pub mod tid;
-pub mod hybrid;
\ No newline at end of file
@@ -33,7 +33,7 @@ impl<'a> AprioriTIDRunner2<'a> {
         }
         c.for_each_range(|a| {
             if a.count >= self.sup {
-                writer.write_set(&a.items);
+                writer.write_set_with_support(&a.items, a.count);
             }
         });
         c.update_tree(self.sup);
@@ -46,7 +46,7 @@ impl<'a> AprioriTIDRunner2<'a> {
             }
             c.for_each_range(|a| {
                 if a.count >= self.sup {
-                    writer.write_set(&a.items);
+                    writer.write_set_with_support(&a.items, a.count);
                 }
             });
             c.update_tree(self.sup);
@@ -73,7 +73,7 @@ impl std::ops::Deref for TransformedDatabase {
     }
 }
 impl TransformedDatabase {
-    pub fn transition(data: &TransactionSet, transition: &mut AprioriTransition, n: usize) -> Self {
+    pub fn transition(data: &[Vec<usize>], transition: &mut AprioriTransition, n: usize) -> Self {
         let mut a = Self::new();
         for d in data.iter() {
             let mut set = AHashSet::new();
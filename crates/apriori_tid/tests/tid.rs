@@ -1,8 +1,8 @@
-use std::{collections::HashSet, path::Path};
+use std::{collections::HashSet, env, fs, path::Path, process};
 
-use apriori::start::FrequentWriter;
+use apriori::{checkpoint::Checkpoints, start::FrequentWriter};
 use apriori_tid::{hybrid::AprioriHybridRunner, tid::AprioriTIDRunner2};
-use tester::test_utils::{Solved, test_generic};
+use tester::test_utils::{Solved, SupportWriter, checkpoint_after, test_generic};
 
 #[test]
 fn test_tid() {
@@ -22,3 +22,20 @@ fn test_hybrid() {
         Solved::new(writer.into_inner())
     });
 }
+#[test]
+fn test_hybrid_resume() {
+    test_generic(Path::new("../../test_files"), |mut t, s| {
+        let mut full = SupportWriter::default();
+        AprioriHybridRunner::new(&mut t, s).run(&mut full);
+        let dir = env::temp_dir().join(format!("hybrid_resume_{}", process::id()));
+        let checkpoints = Checkpoints::new(&dir);
+        let mut out = checkpoint_after(&full.0, 4, s, &checkpoints);
+        let checkpoint = checkpoints.load().unwrap().unwrap();
+        AprioriHybridRunner::new(&mut t, s)
+            .with_checkpoints(checkpoints)
+            .with_resume(checkpoint)
+            .run(&mut out);
+        fs::remove_dir_all(dir).unwrap();
+        Solved::from_reader(&out[..]).unwrap()
+    });
+}
@@ -0,0 +1,210 @@
+use std::{convert::Infallible, time::Instant};
+
+use apriori::{
+    apriori::{apriori_pass_one_counter, apriori_pass_two_counter},
+    array2d::AprioriP2Counter,
+    start::Write,
+    storage::{AprioriCounter, AprioriCounting, AprioriFrequent},
+    transaction_set::{Support, TransactionSet},
+    trie::{TrieCounter, TrieSet},
+};
+use mpi::{
+    collective::SystemOperation,
+    datatype::{Partition, PartitionMut},
+    environment::Universe,
+    traits::{Communicator, CommunicatorCollectives},
+};
+use parallel::traits::{Convertable, ParallelRun};
+
+use crate::main_thread::{Input, PartitionStrategy, global_support};
+
+/// Count Distribution without a main rank.
+/// Every rank generates the same candidates, the local counts are summed with an allreduce,
+/// and every rank finds the frequent itemsets on its own.
+/// Only rank 0 writes the output.
+pub struct CountDistributionAllReduce<'a, T: Write> {
+    data: Input<'a>,
+    sup: Support,
+    writer: &'a mut T,
+}
+
+impl<'a, T: Write> CountDistributionAllReduce<'a, T> {
+    pub fn new(data: &'a TransactionSet, sup: impl Into<Support>, writer: &'a mut T) -> Self {
+        Self {
+            data: Input::Whole(data),
+            sup: sup.into(),
+            writer,
+        }
+    }
+    /// Runs on local, which is only the partition of this rank, like from load_partition
+    pub fn from_partition(
+        local: TransactionSet,
+        sup: impl Into<Support>,
+        writer: &'a mut T,
+    ) -> Self {
+        Self {
+            data: Input::Partition(local),
+            sup: sup.into(),
+            writer,
+        }
+    }
+}
+
+/// Sums v over every rank
+pub(crate) fn all_reduce(v: &[u64], universe: &Universe) -> Vec<u64> {
+    let mut sum = vec![0; v.len()];
+    universe
+        .world()
+        .all_reduce_into(v, &mut sum[..], SystemOperation::sum());
+    sum
+}
+
+/// Sends each element of out to its rank and returns what this rank got, in the order of the ranks.
+/// Every rank has to call it.
+pub(crate) fn exchange(out: Vec<Vec<u64>>, universe: &Universe) -> Vec<u64> {
+    let world = universe.world();
+    let send_counts: Vec<i32> = out.iter().map(|g| g.len() as i32).collect();
+    let mut recv_counts = vec![0i32; send_counts.len()];
+    world.all_to_all_into(&send_counts[..], &mut recv_counts[..]);
+    let displs = |counts: &[i32]| -> Vec<i32> {
+        counts
+            .iter()
+            .scan(0, |acc, &c| {
+                let d = *acc;
+                *acc += c;
+                Some(d)
+            })
+            .collect()
+    };
+    let (send_displs, recv_displs) = (displs(&send_counts), displs(&recv_counts));
+    let send: Vec<u64> = out.into_iter().flatten().collect();
+    let mut received = vec![0u64; recv_counts.iter().sum::<i32>() as usize];
+    let partition = Partition::new(&send[..], &send_counts[..], &send_displs[..]);
+    let mut partition_mut =
+        PartitionMut::new(&mut received[..], &recv_counts[..], &recv_displs[..]);
+    world.all_to_all_varcount_into(&partition, &mut partition_mut);
+    received
+}
+
+/// The globally frequent items with their supports.
+/// Every rank has to call it.
+pub(crate) fn pass_one(local: &TransactionSet, sup: u64, universe: &Universe) -> Vec<(usize, u64)> {
+    let mut counts = vec![0; local.num_items];
+    apriori_pass_one_counter(local, &mut counts);
+    all_reduce(&counts, universe)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, count)| *count >= sup)
+        .collect()
+}
+
+/// The globally frequent pairs of the frequent items p1 with their supports.
+/// Every rank has to call it.
+pub(crate) fn pass_two(
+    local: &TransactionSet,
+    p1: &[usize],
+    sup: u64,
+    universe: &Universe,
+) -> Vec<(Vec<usize>, u64)> {
+    let mut p2 = AprioriP2Counter::new(p1);
+    apriori_pass_two_counter(local, &mut p2);
+    // The layout of the pass two counter only depends on p1
+    let counts = all_reduce(&p2.to_vec(), universe);
+    let mut p2 = AprioriP2Counter::new(p1);
+    p2.add_from_vec(&counts);
+    let mut frequent = Vec::new();
+    p2.for_each(|v, c| {
+        if c >= sup {
+            frequent.push((v.to_vec(), c));
+        }
+    });
+    frequent
+}
+
+/// The globally frequent itemsets of length n joined from prev with their supports.
+/// Every rank has to call it.
+pub(crate) fn pass_n(
+    local: &TransactionSet,
+    prev: &TrieSet,
+    n: usize,
+    sup: u64,
+    universe: &Universe,
+) -> Vec<(Vec<usize>, u64)> {
+    let mut counter: TrieCounter = prev.join_new();
+    // The tries are laid out differently on each rank,
+    // so the counts are exchanged in the sorted order of the candidates.
+    let mut candidates = Vec::new();
+    counter.for_each(|v, _| {
+        if v.len() == n {
+            candidates.push(v.to_vec());
+        }
+    });
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+    candidates.sort_unstable();
+    for d in local.iter() {
+        counter.count(d, n);
+    }
+    let counts: Vec<u64> = candidates
+        .iter()
+        .map(|v| counter.get_count(v).unwrap())
+        .collect();
+    candidates
+        .into_iter()
+        .zip(all_reduce(&counts, universe))
+        .filter(|(_, count)| *count >= sup)
+        .collect()
+}
+
+impl<T: Write> CountDistributionAllReduce<'_, T> {
+    /// Writes the frequent itemsets on rank 0 and returns them as a set
+    fn collect(&mut self, frequent: Vec<(Vec<usize>, u64)>, write: bool) -> TrieSet {
+        let mut set = TrieSet::new();
+        for (v, count) in frequent {
+            if write {
+                self.writer.write_set_with_support(&v, count);
+            }
+            set.insert(&v);
+        }
+        set
+    }
+}
+
+impl<T: Write> ParallelRun for CountDistributionAllReduce<'_, T> {
+    type Error = Infallible;
+
+    fn run(mut self, universe: &Universe) -> Result<(), Self::Error> {
+        let write = universe.world().rank() == 0;
+        let local = self.data.local(PartitionStrategy::default(), universe);
+        let sup = global_support(self.sup, &local, universe);
+
+        let p1 = pass_one(&local, sup, universe);
+        if write {
+            p1.iter()
+                .for_each(|&(n, count)| self.writer.write_set_with_support(&[n], count));
+        }
+        let p1: Vec<usize> = p1.into_iter().map(|(n, _)| n).collect();
+        if p1.is_empty() {
+            return Ok(());
+        }
+
+        let prev_time = Instant::now();
+        let mut prev = self.collect(pass_two(&local, &p1, sup, universe), write);
+        if write {
+            println!("2 {:?}", prev_time.elapsed());
+        }
+
+        for n in 3.. {
+            if prev.is_empty() {
+                break;
+            }
+            let prev_time = Instant::now();
+            prev = self.collect(pass_n(&local, &prev, n, sup, universe), write);
+            if write {
+                println!("{n} {:?}", prev_time.elapsed());
+            }
+        }
+        Ok(())
+    }
+}
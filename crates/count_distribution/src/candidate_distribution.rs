@@ -0,0 +1,258 @@
+use std::{convert::Infallible, time::Instant};
+
+use apriori::{
+    start::Write,
+    storage::{AprioriCounter, AprioriCounting, AprioriFrequent},
+    transaction_set::{Support, TransactionSet},
+    trie::{TrieCounter, TrieSet},
+};
+use mpi::{
+    datatype::PartitionMut,
+    environment::Universe,
+    traits::{Communicator, Root},
+};
+use parallel::traits::ParallelRun;
+
+use crate::{
+    allreduce::{pass_n, pass_one, pass_two},
+    main_thread::{PartitionStrategy, global_support, partition},
+};
+
+/// The default pass at which the ranks stop exchanging counts
+pub const DEFAULT_SWITCH: usize = 3;
+
+/// Candidate Distribution by Agrawal and Shafer.
+/// Runs Count Distribution until the switch pass,
+/// then splits the frequent itemsets into classes by their prefix,
+/// so each rank mines its classes over the whole database without exchanging any more counts.
+/// The itemsets are gathered on rank 0, which writes them.
+pub struct CandidateDistribution<'a, T: Write> {
+    data: &'a TransactionSet,
+    sup: Support,
+    switch: usize,
+    writer: &'a mut T,
+}
+
+impl<'a, T: Write> CandidateDistribution<'a, T> {
+    pub fn new(data: &'a TransactionSet, sup: impl Into<Support>, writer: &'a mut T) -> Self {
+        Self {
+            data,
+            sup: sup.into(),
+            switch: DEFAULT_SWITCH,
+            writer,
+        }
+    }
+    /// Sets the pass that gets generated from the partitioned classes.
+    /// Has to be at least 3, since the classes of pass 2 all share the empty prefix.
+    pub fn with_switch(mut self, switch: usize) -> Self {
+        assert!(switch >= 3, "The switch pass must be at least 3");
+        self.switch = switch;
+        self
+    }
+}
+
+/// Splits the frequent itemsets of prev into classes by the prefix of length prefix_len,
+/// and assigns the classes to size ranks.
+/// A class of m itemsets makes m(m-1)/2 candidates in the next pass,
+/// so the classes go from the largest to the least loaded rank.
+/// Every rank gets the same assignment as the order only depends on prev.
+fn assign_classes(prev: &[Vec<usize>], prefix_len: usize, size: usize) -> Vec<Vec<Vec<usize>>> {
+    let mut classes: Vec<(&[usize], Vec<Vec<usize>>)> = Vec::new();
+    let mut sorted: Vec<&Vec<usize>> = prev.iter().collect();
+    sorted.sort_unstable();
+    for v in sorted {
+        match classes.last_mut() {
+            Some((prefix, class)) if *prefix == &v[..prefix_len] => class.push(v.clone()),
+            _ => classes.push((&v[..prefix_len], vec![v.clone()])),
+        }
+    }
+    let weight = |m: usize| (m * m.saturating_sub(1) / 2) as u64;
+    classes.sort_by(|a, b| weight(b.1.len()).cmp(&weight(a.1.len())).then(a.0.cmp(b.0)));
+    let mut loads = vec![0u64; size];
+    let mut ranks = vec![Vec::new(); size];
+    for (_, class) in classes {
+        let (rank, _) = loads
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, load)| (**load, *i))
+            .unwrap();
+        loads[rank] += weight(class.len());
+        ranks[rank].extend(class);
+    }
+    ranks
+}
+
+impl<T: Write> CandidateDistribution<'_, T> {
+    /// Writes the frequent itemsets on rank 0 and returns them
+    fn collect(&mut self, frequent: Vec<(Vec<usize>, u64)>, write: bool) -> Vec<Vec<usize>> {
+        frequent
+            .into_iter()
+            .map(|(v, count)| {
+                if write {
+                    self.writer.write_set_with_support(&v, count);
+                }
+                v
+            })
+            .collect()
+    }
+    /// Mines every itemset that extends the classes in own, starting at pass n.
+    /// Candidates are only pruned by subsets with the same class prefix,
+    /// since the other classes may belong to other ranks.
+    /// Returns each found itemset as its length, its items and its support.
+    fn mine(&self, own: Vec<Vec<usize>>, n: usize, sup: u64) -> Vec<u64> {
+        let prefix_len = n - 2;
+        let mut found = Vec::new();
+        let mut prev = TrieSet::new();
+        own.iter().for_each(|v| prev.insert(v));
+        for n in n.. {
+            if prev.is_empty() {
+                break;
+            }
+            let mut counter = TrieCounter::new();
+            let mut subset = Vec::with_capacity(n - 1);
+            prev.join_fn(|v| {
+                for i in prefix_len..v.len() {
+                    subset.clear();
+                    subset.extend_from_slice(&v[..i]);
+                    subset.extend_from_slice(&v[(i + 1)..]);
+                    if !prev.contains(&subset) {
+                        return;
+                    }
+                }
+                counter.insert(v);
+            });
+            for d in self.data.iter() {
+                counter.count(d, n);
+            }
+            prev = TrieSet::new();
+            counter.for_each(|v, c| {
+                if v.len() == n && c >= sup {
+                    found.push(v.len() as u64);
+                    found.extend(v.iter().map(|&n| n as u64));
+                    found.push(c);
+                    prev.insert(v);
+                }
+            });
+        }
+        found
+    }
+}
+
+impl<T: Write> ParallelRun for CandidateDistribution<'_, T> {
+    type Error = Infallible;
+
+    fn run(mut self, universe: &Universe) -> Result<(), Self::Error> {
+        let world = universe.world();
+        let write = world.rank() == 0;
+        let local = partition(self.data, PartitionStrategy::default(), universe);
+        let sup = global_support(self.sup, &local, universe);
+
+        let p1 = pass_one(&local, sup, universe);
+        let mut prev: Vec<Vec<usize>> = self.collect(
+            p1.into_iter().map(|(n, count)| (vec![n], count)).collect(),
+            write,
+        );
+        for n in 2..self.switch {
+            if prev.is_empty() {
+                return Ok(());
+            }
+            let prev_time = Instant::now();
+            let frequent = if n == 2 {
+                let p1: Vec<usize> = prev.iter().map(|v| v[0]).collect();
+                pass_two(&local, &p1, sup, universe)
+            } else {
+                let mut set = TrieSet::new();
+                prev.iter().for_each(|v| set.insert(v));
+                pass_n(&local, &set, n, sup, universe)
+            };
+            prev = self.collect(frequent, write);
+            if write {
+                println!("{n} {:?}", prev_time.elapsed());
+            }
+        }
+
+        let prev_time = Instant::now();
+        let mut ranks = assign_classes(&prev, self.switch - 2, world.size() as usize);
+        let own = std::mem::take(&mut ranks[world.rank() as usize]);
+        let found = self.mine(own, self.switch, sup);
+
+        write_found(&found, self.writer, universe);
+        if write {
+            println!("{}.. {:?}", self.switch, prev_time.elapsed());
+        }
+        Ok(())
+    }
+}
+
+/// Gathers the itemsets that every rank found on rank 0, which writes them.
+/// Each itemset in found is its length, its items and its support.
+/// Every rank has to call it.
+pub(crate) fn write_found<T: Write>(found: &[u64], writer: &mut T, universe: &Universe) {
+    let world = universe.world();
+    let root = world.process_at_rank(0);
+    if world.rank() != 0 {
+        root.gather_into(&(found.len() as i32));
+        root.gather_varcount_into(found);
+        return;
+    }
+    let mut counts = vec![0i32; world.size() as usize];
+    root.gather_into_root(&(found.len() as i32), &mut counts[..]);
+    let displs: Vec<i32> = counts
+        .iter()
+        .scan(0, |acc, &c| {
+            let d = *acc;
+            *acc += c;
+            Some(d)
+        })
+        .collect();
+    let mut all = vec![0u64; counts.iter().sum::<i32>() as usize];
+    let mut partition = PartitionMut::new(&mut all[..], &counts[..], &displs[..]);
+    root.gather_varcount_into_root(found, &mut partition);
+    let mut i = 0;
+    let mut v = Vec::new();
+    while i < all.len() {
+        let len = all[i] as usize;
+        v.clear();
+        v.extend(all[(i + 1)..(i + 1 + len)].iter().map(|&n| n as usize));
+        writer.write_set_with_support(&v, all[i + 1 + len]);
+        i += len + 2;
+    }
+}
+
+/// Collects the written itemsets in the format of write_found
+#[derive(Default)]
+pub(crate) struct Found(pub Vec<u64>);
+impl Write for Found {
+    fn write_set_with_support(&mut self, v: &[usize], sup: u64) {
+        self.0.push(v.len() as u64);
+        self.0.extend(v.iter().map(|&n| n as u64));
+        self.0.push(sup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assign_classes;
+
+    #[test]
+    fn test_assign_classes() {
+        let prev = vec![
+            vec![1, 2],
+            vec![1, 3],
+            vec![1, 4],
+            vec![2, 3],
+            vec![2, 4],
+            vec![3, 4],
+            vec![4, 5],
+        ];
+        let ranks = assign_classes(&prev, 1, 2);
+        // The class of 1 makes 3 candidates, the others at most 1
+        assert_eq!(ranks[0], vec![vec![1, 2], vec![1, 3], vec![1, 4]]);
+        assert_eq!(
+            ranks[1],
+            vec![vec![2, 3], vec![2, 4], vec![3, 4], vec![4, 5]]
+        );
+        let ranks = assign_classes(&prev, 1, 1);
+        assert_eq!(ranks[0].len(), prev.len());
+    }
+}
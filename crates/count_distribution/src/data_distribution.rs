@@ -0,0 +1,198 @@
+use std::{convert::Infallible, time::Instant};
+
+use apriori::{
+    apriori::apriori_pass_one_counter,
+    start::Write,
+    storage::{AprioriCounter, AprioriCounting, AprioriFrequent},
+    transaction_set::{Support, TransactionSet},
+    trie::{TrieCounter, TrieSet},
+};
+use mpi::{
+    collective::SystemOperation,
+    datatype::PartitionMut,
+    environment::Universe,
+    traits::{Communicator, CommunicatorCollectives, Destination, Source},
+};
+use parallel::traits::ParallelRun;
+
+use crate::main_thread::{Input, PartitionStrategy, global_support};
+
+/// Data Distribution by Agrawal and Shafer.
+/// Each rank only counts the candidates it owns, against every partition of the database,
+/// as the partitions get passed around a ring of the ranks.
+/// The frequent itemsets of each pass are then gathered on every rank for the next join.
+/// Only rank 0 writes the output.
+pub struct DataDistribution<'a, T: Write> {
+    data: Input<'a>,
+    sup: Support,
+    writer: &'a mut T,
+}
+
+impl<'a, T: Write> DataDistribution<'a, T> {
+    pub fn new(data: &'a TransactionSet, sup: impl Into<Support>, writer: &'a mut T) -> Self {
+        Self {
+            data: Input::Whole(data),
+            sup: sup.into(),
+            writer,
+        }
+    }
+    /// Runs on local, which is only the partition of this rank, like from load_partition
+    pub fn from_partition(
+        local: TransactionSet,
+        sup: impl Into<Support>,
+        writer: &'a mut T,
+    ) -> Self {
+        Self {
+            data: Input::Partition(local),
+            sup: sup.into(),
+            writer,
+        }
+    }
+}
+
+/// The rank that counts the candidate v.
+/// Has to be the same on every rank, so it does not use a randomly seeded hasher.
+fn owner(v: &[usize], size: usize) -> usize {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &n in v {
+        h ^= n as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    (h % size as u64) as usize
+}
+
+/// Flattens data into the length of each transaction followed by its items
+fn encode(data: &TransactionSet) -> Vec<u64> {
+    let mut v = Vec::with_capacity(data.len() + data.size);
+    for d in data.iter() {
+        v.push(d.len() as u64);
+        v.extend(d.iter().map(|&n| n as u64));
+    }
+    v
+}
+
+/// Counts every transaction of an encoded partition into counter
+fn count_encoded(v: &[u64], counter: &mut TrieCounter, n: usize, buf: &mut Vec<usize>) {
+    let mut i = 0;
+    while i < v.len() {
+        let len = v[i] as usize;
+        buf.clear();
+        buf.extend(v[(i + 1)..(i + 1 + len)].iter().map(|&n| n as usize));
+        counter.count(buf, n);
+        i += len + 1;
+    }
+}
+
+impl<T: Write> DataDistribution<'_, T> {
+    /// Counts the owned candidates in counter against the partitions of every rank
+    fn count(counter: &mut TrieCounter, local: &[u64], n: usize, universe: &Universe) {
+        let world = universe.world();
+        let (rank, size) = (world.rank(), world.size());
+        let next = world.process_at_rank((rank + 1) % size);
+        let prev = world.process_at_rank((rank + size - 1) % size);
+        let mut buf = Vec::new();
+        count_encoded(local, counter, n, &mut buf);
+        let mut current = local.to_vec();
+        for _ in 1..size {
+            let (received, _) = mpi::request::scope(|scope| {
+                let sent = next.immediate_send(scope, &current[..]);
+                let received = prev.receive_vec::<u64>();
+                sent.wait();
+                received
+            });
+            count_encoded(&received, counter, n, &mut buf);
+            current = received;
+        }
+    }
+    /// Gathers the frequent itemsets of length n that each rank found in its counter.
+    /// Each itemset is followed by its support.
+    fn gather(counter: &TrieCounter, n: usize, sup: u64, universe: &Universe) -> Vec<u64> {
+        let world = universe.world();
+        let mut local = Vec::new();
+        counter.for_each(|v, c| {
+            if v.len() == n && c >= sup {
+                local.extend(v.iter().map(|&n| n as u64));
+                local.push(c);
+            }
+        });
+        let mut counts = vec![0i32; world.size() as usize];
+        world.all_gather_into(&(local.len() as i32), &mut counts[..]);
+        let displs: Vec<i32> = counts
+            .iter()
+            .scan(0, |acc, &c| {
+                let d = *acc;
+                *acc += c;
+                Some(d)
+            })
+            .collect();
+        let mut all = vec![0u64; counts.iter().sum::<i32>() as usize];
+        let mut partition = PartitionMut::new(&mut all[..], &counts[..], &displs[..]);
+        world.all_gather_varcount_into(&local[..], &mut partition);
+        all
+    }
+}
+
+impl<T: Write> ParallelRun for DataDistribution<'_, T> {
+    type Error = Infallible;
+
+    fn run(mut self, universe: &Universe) -> Result<(), Self::Error> {
+        let world = universe.world();
+        let (rank, size) = (world.rank() as usize, world.size() as usize);
+        let write = rank == 0;
+        let local = self.data.local(PartitionStrategy::default(), universe);
+        let sup = global_support(self.sup, &local, universe);
+
+        // The items are few enough for every rank to count all of them
+        let mut counts = vec![0; local.num_items];
+        apriori_pass_one_counter(&local, &mut counts);
+        let mut total = vec![0; counts.len()];
+        world.all_reduce_into(&counts[..], &mut total[..], SystemOperation::sum());
+        let mut prev = TrieSet::new();
+        for (n, &count) in total.iter().enumerate() {
+            if count >= sup {
+                if write {
+                    self.writer.write_set_with_support(&[n], count);
+                }
+                prev.insert(&[n]);
+            }
+        }
+
+        let local = encode(&local);
+        for n in 2.. {
+            if prev.is_empty() {
+                break;
+            }
+            let prev_time = Instant::now();
+            let mut counter = TrieCounter::new();
+            let mut subset = Vec::with_capacity(n - 1);
+            prev.join_fn(|v| {
+                if owner(v, size) != rank {
+                    return;
+                }
+                for i in 0..v.len() {
+                    subset.clear();
+                    subset.extend_from_slice(&v[..i]);
+                    subset.extend_from_slice(&v[(i + 1)..]);
+                    if !prev.contains(&subset) {
+                        return;
+                    }
+                }
+                counter.insert(v);
+            });
+            Self::count(&mut counter, &local, n, universe);
+            let frequent = Self::gather(&counter, n, sup, universe);
+            prev = TrieSet::new();
+            for record in frequent.chunks_exact(n + 1) {
+                let v: Vec<usize> = record[..n].iter().map(|&n| n as usize).collect();
+                if write {
+                    self.writer.write_set_with_support(&v, record[n]);
+                }
+                prev.insert(&v);
+            }
+            if write {
+                println!("{n} {:?}", prev_time.elapsed());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+use std::{collections::BTreeMap, convert::Infallible, time::Instant};
+
+use apriori::{
+    eclat::{TidList, intersect, mine_class, vertical},
+    start::Write,
+    transaction_set::{Support, TransactionSet},
+};
+use mpi::{
+    environment::Universe,
+    traits::{Communicator, CommunicatorCollectives},
+};
+use parallel::traits::ParallelRun;
+
+use crate::{
+    allreduce::{exchange, pass_one, pass_two},
+    candidate_distribution::{Found, write_found},
+    main_thread::{Input, PartitionStrategy, global_support},
+};
+
+/// Eclat over MPI by Zaki et al.
+/// The frequent pairs are counted like Count Distribution and split into classes by their first item.
+/// Each rank gets whole classes along with the tid lists of their pairs from every partition,
+/// then mines them without any more communication.
+/// The itemsets are gathered on rank 0, which writes them.
+pub struct DistributedEclat<'a, T: Write> {
+    data: Input<'a>,
+    sup: Support,
+    writer: &'a mut T,
+}
+
+impl<'a, T: Write> DistributedEclat<'a, T> {
+    pub fn new(data: &'a TransactionSet, sup: impl Into<Support>, writer: &'a mut T) -> Self {
+        Self {
+            data: Input::Whole(data),
+            sup: sup.into(),
+            writer,
+        }
+    }
+    /// Runs on local, which is only the partition of this rank, like from load_partition
+    pub fn from_partition(
+        local: TransactionSet,
+        sup: impl Into<Support>,
+        writer: &'a mut T,
+    ) -> Self {
+        Self {
+            data: Input::Partition(local),
+            sup: sup.into(),
+            writer,
+        }
+    }
+}
+
+/// The rank of the class of every item that starts a frequent pair.
+/// Each pair of a class gets intersected with the pairs after it,
+/// so a class weighs the supports of its pairs times the number of pairs after them.
+/// The classes go from the heaviest to the least loaded rank.
+/// Every rank gets the same schedule as it only depends on pairs.
+fn schedule(pairs: &[(Vec<usize>, u64)], num_items: usize, size: usize) -> Vec<usize> {
+    let mut classes: BTreeMap<usize, Vec<u64>> = BTreeMap::new();
+    for (v, count) in pairs {
+        classes.entry(v[0]).or_default().push(*count);
+    }
+    let mut weights: Vec<(usize, u64)> = classes
+        .into_iter()
+        .map(|(prefix, counts)| {
+            let weight = counts
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| c * (counts.len() - 1 - i) as u64)
+                .sum();
+            (prefix, weight)
+        })
+        .collect();
+    weights.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut owners = vec![usize::MAX; num_items];
+    let mut loads = vec![0u64; size];
+    for (prefix, weight) in weights {
+        let (rank, _) = loads
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, load)| (**load, *i))
+            .unwrap();
+        loads[rank] += weight;
+        owners[prefix] = rank;
+    }
+    owners
+}
+
+/// The tid lists of the pairs in local for each rank, as the pair, the length and the tids.
+/// The tids of local start at offset.
+fn pair_tids(
+    local: &TransactionSet,
+    pairs: &[(Vec<usize>, u64)],
+    owners: &[usize],
+    offset: usize,
+    size: usize,
+) -> Vec<Vec<u64>> {
+    let items = vertical(local);
+    let mut out = vec![Vec::new(); size];
+    for (v, _) in pairs {
+        let tids = intersect(&items[v[0]], &items[v[1]]);
+        if tids.is_empty() {
+            continue;
+        }
+        let out = &mut out[owners[v[0]]];
+        out.extend([v[0] as u64, v[1] as u64, tids.len() as u64]);
+        out.extend(tids.iter().map(|&t| (t + offset) as u64));
+    }
+    out
+}
+
+/// Mines the classes of rank from the tid lists of pair_tids that it received from every rank
+fn mine_own(
+    received: &[u64],
+    pairs: &[(Vec<usize>, u64)],
+    owners: &[usize],
+    rank: usize,
+    sup: u64,
+    out: &mut impl Write,
+) {
+    let mut own: BTreeMap<(usize, usize), TidList> = pairs
+        .iter()
+        .filter(|(v, _)| owners[v[0]] == rank)
+        .map(|(v, _)| ((v[0], v[1]), TidList::new()))
+        .collect();
+    // The parts of each tid list arrive in the order of the ranks, so they stay sorted
+    let mut i = 0;
+    while i < received.len() {
+        let (a, b, len) = (
+            received[i] as usize,
+            received[i + 1] as usize,
+            received[i + 2] as usize,
+        );
+        own.get_mut(&(a, b))
+            .unwrap()
+            .extend(received[(i + 3)..(i + 3 + len)].iter().map(|&t| t as usize));
+        i += len + 3;
+    }
+    let mut prefix = Vec::new();
+    let mut class = Vec::new();
+    let mut own = own.into_iter().peekable();
+    while let Some(((a, b), tids)) = own.next() {
+        class.push((b, tids));
+        if own.peek().is_none_or(|((next, _), _)| *next != a) {
+            prefix.push(a);
+            mine_class(sup, &mut prefix, &class, out);
+            prefix.pop();
+            class.clear();
+        }
+    }
+}
+
+impl<T: Write> ParallelRun for DistributedEclat<'_, T> {
+    type Error = Infallible;
+
+    fn run(mut self, universe: &Universe) -> Result<(), Self::Error> {
+        let world = universe.world();
+        let (rank, size) = (world.rank() as usize, world.size() as usize);
+        let write = rank == 0;
+        let local = self.data.local(PartitionStrategy::default(), universe);
+        let sup = global_support(self.sup, &local, universe);
+
+        let p1 = pass_one(&local, sup, universe);
+        if write {
+            p1.iter()
+                .for_each(|&(n, count)| self.writer.write_set_with_support(&[n], count));
+        }
+        let p1: Vec<usize> = p1.into_iter().map(|(n, _)| n).collect();
+        if p1.is_empty() {
+            return Ok(());
+        }
+        let prev_time = Instant::now();
+        let pairs = pass_two(&local, &p1, sup, universe);
+        let owners = schedule(&pairs, local.num_items, size);
+        // The tids of each partition follow the ones of the ranks before it
+        let mut lens = vec![0u64; size];
+        world.all_gather_into(&(local.len() as u64), &mut lens[..]);
+        let offset = lens[..rank].iter().sum::<u64>() as usize;
+        let received = exchange(pair_tids(&local, &pairs, &owners, offset, size), universe);
+        if write {
+            println!("2 {:?}", prev_time.elapsed());
+        }
+
+        let prev_time = Instant::now();
+        let mut found = Found::default();
+        mine_own(&received, &pairs, &owners, rank, sup, &mut found);
+        write_found(&found.0, self.writer, universe);
+        if write {
+            println!("3.. {:?}", prev_time.elapsed());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use apriori::{
+        eclat::Eclat,
+        start::{Apriori, FrequentWriter},
+        transaction_set::TransactionSet,
+    };
+
+    use super::{mine_own, pair_tids, schedule};
+
+    #[test]
+    fn test_schedule() {
+        let pairs = vec![
+            (vec![0, 1], 4),
+            (vec![0, 2], 3),
+            (vec![0, 3], 2),
+            (vec![1, 2], 5),
+            (vec![1, 3], 1),
+            (vec![2, 3], 6),
+        ];
+        // The class of 0 weighs 4 * 2 + 3, the class of 1 weighs 5 and the class of 2 weighs 0
+        let owners = schedule(&pairs, 5, 2);
+        assert_eq!(owners, vec![0, 1, 1, usize::MAX, usize::MAX]);
+        let owners = schedule(&pairs, 5, 1);
+        assert_eq!(owners, vec![0, 0, 0, usize::MAX, usize::MAX]);
+    }
+    #[test]
+    fn test_pair_tids() {
+        let local = TransactionSet::new(vec![vec![0, 1, 2], vec![1, 2], vec![0, 2]], 3);
+        let pairs = vec![(vec![0, 1], 1), (vec![0, 2], 2), (vec![1, 2], 2)];
+        let out = pair_tids(&local, &pairs, &[1, 0, usize::MAX], 10, 2);
+        assert_eq!(out[0], vec![1, 2, 2, 10, 11]);
+        assert_eq!(out[1], vec![0, 1, 1, 10, 0, 2, 2, 10, 12]);
+    }
+    #[test]
+    fn test_mine_partitions() {
+        let partitions = [
+            TransactionSet::new(vec![vec![0, 1, 2], vec![0, 1], vec![1, 2, 3]], 4),
+            TransactionSet::new(vec![vec![0, 1, 2, 3], vec![3], vec![0, 2, 3]], 4),
+        ];
+        let whole = TransactionSet::new(
+            partitions.iter().flat_map(|p| p.iter().cloned()).collect(),
+            4,
+        );
+        let mut expected = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        Eclat::new(&whole, 2).run(&mut expected);
+        // Rank 0 writes the items itself
+        let mut expected = expected.into_inner();
+        expected.retain(|v| v.len() > 1);
+        let pairs: Vec<(Vec<usize>, u64)> = expected
+            .iter()
+            .filter(|v| v.len() == 2)
+            .map(|v| (v.clone(), 2))
+            .collect();
+        let owners = schedule(&pairs, 4, 2);
+        let sent: Vec<_> = partitions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| pair_tids(p, &pairs, &owners, i * 3, 2))
+            .collect();
+        let mut s = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        for rank in 0..2 {
+            let received: Vec<u64> = sent.iter().flat_map(|out| out[rank].clone()).collect();
+            mine_own(&received, &pairs, &owners, rank, 2, &mut s);
+        }
+        assert_eq!(s.into_inner(), expected);
+    }
+}
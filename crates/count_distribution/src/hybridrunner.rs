@@ -1,105 +1,253 @@
+use std::thread;
+
 use apriori::{
     apriori::apriori_pass_two_counter,
     array2d::AprioriP2Counter,
+    checkpoint::{Checkpoint, Checkpoints},
     start::Write,
-    storage::AprioriFrequent,
-    transaction_set::TransactionSet,
+    storage::{AprioriCounter, AprioriFrequent},
+    threaded::{count_parallel, default_threads, split},
+    transaction_set::{Support, TransactionSet},
     trie::{TrieCounter, TrieSet},
 };
 use apriori_tid::hybrid::AprioriHybridContainer;
-use mpi::{
-    environment::Universe,
-    traits::{Communicator, Destination, Source},
-};
+use mpi::{environment::Universe, traits::Communicator};
 use parallel::traits::{Convertable, ParallelRun};
 
-use crate::main_thread::{MainRunner, ParallelCounting};
+use crate::{
+    allreduce::{all_reduce, pass_one},
+    main_thread::{
+        Input, MainRunner, ParallelCounting, PartitionStrategy, global_support, rank_threads,
+    },
+    protocol::{HelperError, serve},
+};
+
+/// When the ranks start counting with the transformed database of AprioriTID
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Switch {
+    /// Once the candidates of a pass occur fewer times over every rank
+    /// than there are items in the whole database, like AprioriHybrid
+    #[default]
+    Global,
+    /// From pass 3 on, like AprioriTID
+    Always,
+}
 
 pub struct CountDistributionHybrid<'a, T: Write> {
-    data: &'a TransactionSet,
-    sup: u64,
+    data: Input<'a>,
+    sup: Support,
+    threads: usize,
+    strategy: PartitionStrategy,
+    switch: Switch,
+    checkpoints: Option<Checkpoints>,
+    resume: Option<Checkpoint>,
     writer: &'a mut T,
 }
 
 impl<'a, T: Write> CountDistributionHybrid<'a, T> {
-    pub fn new(data: &'a TransactionSet, sup: u64, writer: &'a mut T) -> Self {
-        Self { data, sup, writer }
+    pub fn new(data: &'a TransactionSet, sup: impl Into<Support>, writer: &'a mut T) -> Self {
+        Self {
+            data: Input::Whole(data),
+            sup: sup.into(),
+            threads: 1,
+            strategy: PartitionStrategy::default(),
+            switch: Switch::default(),
+            checkpoints: None,
+            resume: None,
+            writer,
+        }
+    }
+    /// Runs on local, which is only the partition of this rank, like from load_partition
+    pub fn from_partition(
+        local: TransactionSet,
+        sup: impl Into<Support>,
+        writer: &'a mut T,
+    ) -> Self {
+        Self {
+            data: Input::Partition(local),
+            sup: sup.into(),
+            threads: 1,
+            strategy: PartitionStrategy::default(),
+            switch: Switch::default(),
+            checkpoints: None,
+            resume: None,
+            writer,
+        }
+    }
+    /// Sets the number of threads that count the partition of each rank,
+    /// or every available core if threads is 0.
+    /// Each rank counts with 1 thread if MPI was started below Threading::Funneled.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = if threads == 0 {
+            default_threads()
+        } else {
+            threads
+        };
+        self
+    }
+    /// Sets how the whole database gets split between the ranks.
+    /// Does nothing if the runner was made from a partition.
+    pub fn with_partition(mut self, strategy: PartitionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+    /// Sets when every rank switches to AprioriTID
+    pub fn with_switch(mut self, switch: Switch) -> Self {
+        self.switch = switch;
+        self
+    }
+    /// Makes rank 0 save a checkpoint after every pass from 2 on
+    pub fn with_checkpoints(mut self, checkpoints: Checkpoints) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+    /// Makes every rank continue after the pass of checkpoint,
+    /// which has to be made with the same support.
+    /// Only rank 0 needs it, and its writer already has the itemsets written up to it.
+    pub fn with_resume(mut self, checkpoint: Checkpoint) -> Self {
+        self.resume = Some(checkpoint);
+        self
     }
 }
 
 impl<T: Write> ParallelRun for CountDistributionHybrid<'_, T> {
-    fn run(self, universe: &Universe) {
-        let size = universe.world().size();
-        assert!(size > 1, "Rank must be at least 2");
+    type Error = HelperError;
+
+    fn run(mut self, universe: &Universe) -> Result<(), Self::Error> {
         let rank = universe.world().rank();
+        let local = self.data.local(self.strategy, universe);
+        let threads = rank_threads(self.threads, universe);
+        let sup = global_support(self.sup, &local, universe);
+        let items = all_reduce(&[local.size as u64], universe)[0];
+        let p1 = pass_one(&local, sup, universe);
+        // With a single rank, rank 0 counts the whole database through its own MainHelper
         if rank == 0 {
             let mut a = MainRunner::new(
-                self.sup,
+                sup,
                 self.writer,
                 universe,
-                MainHelper::new(self.data, universe, self.sup),
-            );
-            let b = a.preprocess(self.data);
-            a.run(b);
+                MainHelper::new(local, sup, threads).with_switch(self.switch, items),
+            )
+            .with_checkpoints(self.checkpoints)
+            .with_resume(self.resume);
+            let b = a.preprocess(p1);
+            a.run(b)
         } else {
-            let mut a = HelperRunner::new(self.data, universe, self.sup);
+            let mut a = HelperRunner::new(local, universe, sup, threads);
             a.run();
+            Ok(())
         }
     }
 }
 
 struct MainHelper {
+    /// The partition of this rank, split between its threads
     data: TransactionSet,
-    container: AprioriHybridContainer,
+    threads: usize,
+    /// The candidates of each thread, which switches to AprioriTID on its own
+    containers: Vec<AprioriHybridContainer>,
+    /// The counts of every thread added together
+    counter: TrieCounter,
     sup: u64,
+    /// Whether the containers hold the candidates of a previous pass
+    started: bool,
+    switch: Switch,
+    /// The number of items in the whole database
+    items: u64,
 }
 impl MainHelper {
-    pub fn new(data: &TransactionSet, uni: &Universe, sup: u64) -> Self {
-        let world = uni.world();
-        let count = data.len() / (world.size()) as usize;
-        let thread = world.rank() as usize;
-        let slice = if world.rank() == world.size() - 1 {
-            &data.transactions[(count * thread)..data.len()]
-        } else {
-            &data[(count * thread)..(count * (thread + 1))]
-        };
-        let data = TransactionSet::new(slice.to_vec(), data.num_items);
-
+    pub fn new(data: TransactionSet, sup: u64, threads: usize) -> Self {
         Self {
+            containers: split(&data, threads)
+                .iter()
+                .map(|_| AprioriHybridContainer::new(TrieCounter::new(), 0))
+                .collect(),
             data,
-            container: AprioriHybridContainer::new(TrieCounter::new(), 0),
+            threads,
+            counter: TrieCounter::new(),
             sup,
+            started: false,
+            switch: Switch::Global,
+            items: 0,
         }
     }
+    /// Only needed on rank 0, which decides when every rank switches
+    fn with_switch(mut self, switch: Switch, items: u64) -> Self {
+        self.switch = switch;
+        self.items = items;
+        self
+    }
 }
 impl ParallelCounting for MainHelper {
-    fn count(&mut self, set: &TrieSet, n: usize) -> Vec<u64> {
-        if n == 3 {
-            let mut counter = TrieCounter::new();
-            set.for_each(|v| {
-                counter.add(v, self.sup);
-            });
-            self.container = AprioriHybridContainer::new(counter, self.sup);
-        } else {
-            self.container.set(set);
+    fn count(&mut self, set: &TrieSet, n: usize, tid: bool) -> Vec<u64> {
+        let sup = self.sup;
+        // The first pass is 3, or the one after the checkpoint of a resumed run
+        let fresh = !std::mem::replace(&mut self.started, true);
+        let count_part = |data: &[Vec<usize>], container: &mut AprioriHybridContainer| {
+            if fresh {
+                let mut counter = TrieCounter::new();
+                set.for_each(|v| {
+                    counter.add(v, sup);
+                });
+                *container = AprioriHybridContainer::new(counter, sup);
+            } else {
+                container.set(set);
+            }
+            container.run_switched(data, n, tid);
+            container.to_vec()
+        };
+        let parts = split(&self.data, self.threads);
+        // A single partition gets counted on this thread
+        let counts: Vec<Vec<u64>> =
+            if let ([data], [container]) = (&parts[..], &mut self.containers[..]) {
+                vec![count_part(data, container)]
+            } else {
+                thread::scope(|s| {
+                    let handles: Vec<_> = parts
+                        .into_iter()
+                        .zip(self.containers.iter_mut())
+                        .map(|(data, container)| s.spawn(move || count_part(data, container)))
+                        .collect();
+                    handles.into_iter().map(|h| h.join().unwrap()).collect()
+                })
+            };
+        self.counter = TrieCounter::new();
+        for v in &counts {
+            self.counter.add_from_vec(v);
         }
-        self.container.run(&mut self.data, n);
-        self.container.to_vec()
+        self.counter.to_vec()
     }
     fn count_2(&mut self, prev: &[usize]) -> Vec<u64> {
-        let mut p2 = AprioriP2Counter::new(prev);
-        apriori_pass_two_counter(&self.data, &mut p2);
+        let mut p2 = count_parallel(
+            &split(&self.data, self.threads),
+            || AprioriP2Counter::new(prev),
+            apriori_pass_two_counter,
+        );
         p2.to_vec()
     }
 
+    fn occurrences(&self) -> u64 {
+        self.containers
+            .iter()
+            .fold(0u64, |acc, c| acc.saturating_add(c.occurrences()))
+    }
+
+    fn switch(&self, occurrences: u64) -> bool {
+        match self.switch {
+            Switch::Global => occurrences < self.items,
+            Switch::Always => true,
+        }
+    }
+
     fn add(&mut self, v: &[u64]) {
-        self.container.add_from_vec(v);
+        self.counter.add_from_vec(v);
     }
 
-    fn frequent(&mut self, sup: u64) -> TrieSet {
+    fn frequent(&mut self, sup: u64, out: &mut impl Write) -> TrieSet {
         let mut set = TrieSet::new();
-        self.container.for_each(|v, c| {
+        self.counter.for_each(|v, c| {
             if c >= sup {
+                out.write_set_with_support(v, c);
                 set.insert(v);
             }
         });
@@ -113,27 +261,11 @@ struct HelperRunner<'a> {
 }
 
 impl<'a> HelperRunner<'a> {
-    pub fn new(data: &TransactionSet, uni: &'a Universe, sup: u64) -> Self {
-        let counter = MainHelper::new(data, uni, sup);
+    pub fn new(data: TransactionSet, uni: &'a Universe, sup: u64, threads: usize) -> Self {
+        let counter = MainHelper::new(data, sup, threads);
         Self { counter, uni }
     }
     fn run(&mut self) {
-        for n in 2.. {
-            let a: (Vec<u64>, mpi::point_to_point::Status) =
-                self.uni.world().process_at_rank(0).receive_vec();
-            if a.0[0] == u64::MAX {
-                break;
-            }
-            if n == 2 {
-                let a: Vec<_> = a.0.into_iter().map(|n| n as usize).collect();
-                let v = self.counter.count_2(&a);
-                self.uni.world().process_at_rank(0).send(&v);
-            } else {
-                let mut trie = TrieSet::new();
-                trie.add_from_vec(&a.0);
-                let v = self.counter.count(&trie, n);
-                self.uni.world().process_at_rank(0).send(&v);
-            }
-        }
+        serve(&mut self.counter, self.uni);
     }
 }
@@ -1,3 +1,9 @@
-pub mod runner;
+pub mod allreduce;
+pub mod candidate_distribution;
+pub mod data_distribution;
+pub mod distributed_eclat;
 pub mod hybridrunner;
-pub mod main_thread;
\ No newline at end of file
+pub mod main_thread;
+pub mod pfp;
+pub mod protocol;
+pub mod runner;
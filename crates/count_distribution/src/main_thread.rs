@@ -1,98 +1,488 @@
-use std::time::Instant;
+use std::{
+    fs::File,
+    ops::Range,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
 
 use apriori::{
-    apriori::apriori_pass_one,
     array2d::AprioriP2Counter,
+    checkpoint::{Checkpoint, Checkpoints, CountingWriter},
     start::Write,
     storage::{AprioriCounter, AprioriFrequent},
-    transaction_set::TransactionSet,
-    trie::TrieSet,
+    transaction_set::{BadLines, DatError, Support, TransactionSet},
+    trie::{TrieCounter, TrieSet},
 };
 use mpi::{
-    environment::Universe,
-    traits::{Communicator, Destination, Source},
+    Threading,
+    collective::SystemOperation,
+    environment::{self, Universe},
+    point_to_point::Status,
+    traits::{Communicator, CommunicatorCollectives, Destination},
 };
 use parallel::traits::Convertable;
 
+use crate::protocol::{HelperError, Message, ProtocolError};
+
+/// The database of a runner
+pub enum Input<'a> {
+    /// The whole database, which every rank has loaded
+    Whole(&'a TransactionSet),
+    /// Only the partition of this rank, like from load_partition
+    Partition(TransactionSet),
+}
+impl Input<'_> {
+    /// The part of the database that this rank counts, which a partition is moved out of.
+    /// Only the whole database gets split by strategy.
+    pub(crate) fn local(&mut self, strategy: PartitionStrategy, uni: &Universe) -> TransactionSet {
+        match self {
+            Input::Whole(data) => partition(data, strategy, uni),
+            Input::Partition(data) => std::mem::take(data),
+        }
+    }
+}
+
+/// Loads only the lines of the .dat file at path that start in the byte range of this rank.
+/// The ranks agree on num_items, and the number of skipped lines is summed over every rank.
+/// Every rank has to call it, and they all fail together if one of them does.
+pub fn load_partition(
+    path: &Path,
+    bad_lines: BadLines,
+    uni: &Universe,
+) -> Result<(TransactionSet, usize), DatError> {
+    let world = uni.world();
+    let (rank, size) = (world.rank() as u64, world.size() as u64);
+    let loaded = File::open(path).map_err(DatError::IO).and_then(|file| {
+        let len = file.metadata().map_err(DatError::IO)?.len();
+        let range = (len * rank / size)..(len * (rank + 1) / size);
+        TransactionSet::from_dat_range(file, range, bad_lines)
+    });
+    // A rank that returned on its own would leave the others in the reductions
+    let mut failed = 0i32;
+    world.all_reduce_into(
+        &(loaded.is_err() as i32),
+        &mut failed,
+        SystemOperation::max(),
+    );
+    let (mut data, skipped) = match loaded {
+        Ok(_) if failed != 0 => return Err(DatError::OtherRank),
+        loaded => loaded?,
+    };
+    let mut num_items = 0;
+    world.all_reduce_into(&data.num_items, &mut num_items, SystemOperation::max());
+    data.num_items = num_items;
+    let mut total = 0;
+    world.all_reduce_into(&skipped, &mut total, SystemOperation::sum());
+    Ok((data, total))
+}
+
+/// The number of transactions over every rank, given the partition of this rank.
+/// Every rank has to call it.
+pub fn global_len(local: &TransactionSet, uni: &Universe) -> u64 {
+    let mut total = 0u64;
+    uni.world()
+        .all_reduce_into(&(local.len() as u64), &mut total, SystemOperation::sum());
+    total
+}
+
+/// How the whole database gets split between the ranks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PartitionStrategy {
+    /// The same number of transactions on each rank
+    #[default]
+    EqualCount,
+    /// The same number of items on each rank
+    EqualItems,
+    /// The same number of subsets of the first passes on each rank,
+    /// which is the work of counting long transactions
+    CostModel,
+}
+/// The passes whose subsets the cost model adds up,
+/// since the later passes are mostly pruned by the candidates
+const COST_PASSES: u64 = 3;
+impl PartitionStrategy {
+    /// The estimated work of counting the transaction t
+    fn weight(self, t: &[usize]) -> u64 {
+        let len = t.len() as u64;
+        match self {
+            PartitionStrategy::EqualCount => 1,
+            // The empty transactions still have to be read
+            PartitionStrategy::EqualItems => len + 1,
+            // In u128, where len choose k for the first passes cannot overflow,
+            // and only the total gets saturated
+            PartitionStrategy::CostModel => {
+                let len = len as u128;
+                let mut choose = 1u128;
+                let mut total = 1u128;
+                for k in 1..=(COST_PASSES as u128).min(len) {
+                    choose = choose * (len - k + 1) / k;
+                    total += choose;
+                }
+                u64::try_from(total).unwrap_or(u64::MAX)
+            }
+        }
+    }
+    /// Splits data into parts consecutive ranges of about the same total weight
+    fn ranges(self, data: &TransactionSet, parts: usize) -> Vec<Range<usize>> {
+        let weights: Vec<u64> = data.iter().map(|t| self.weight(t)).collect();
+        let total: u128 = weights.iter().map(|&w| w as u128).sum();
+        let mut ranges = Vec::with_capacity(parts);
+        let (mut start, mut end, mut prefix) = (0, 0, 0u128);
+        for part in 1..=parts {
+            let target = total * part as u128 / parts as u128;
+            while end < weights.len() && prefix < target {
+                prefix += weights[end] as u128;
+                end += 1;
+            }
+            ranges.push(start..end);
+            start = end;
+        }
+        ranges
+    }
+}
+/// Parses one of equal-count, equal-items or cost-model
+impl std::str::FromStr for PartitionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equal-count" => Ok(Self::EqualCount),
+            "equal-items" => Ok(Self::EqualItems),
+            "cost-model" => Ok(Self::CostModel),
+            _ => Err(format!(
+                "{s} is not one of equal-count, equal-items, cost-model"
+            )),
+        }
+    }
+}
+
+/// The part of data that this rank counts
+pub(crate) fn partition(
+    data: &TransactionSet,
+    strategy: PartitionStrategy,
+    uni: &Universe,
+) -> TransactionSet {
+    let world = uni.world();
+    let range = strategy
+        .ranges(data, world.size() as usize)
+        .swap_remove(world.rank() as usize);
+    TransactionSet::new(data[range].to_vec(), data.num_items)
+}
+
+/// Resolves sup against the whole database, given the partition of this rank.
+/// Every rank has to call it.
+pub(crate) fn global_support(sup: Support, local: &TransactionSet, uni: &Universe) -> u64 {
+    sup.resolve(global_len(local, uni))
+}
+
 pub trait ParallelCounting {
-    fn count(&mut self, set: &TrieSet, n: usize) -> Vec<u64>;
+    /// Counts the candidates joined from set, with the transformed database of AprioriTID if tid
+    fn count(&mut self, set: &TrieSet, n: usize, tid: bool) -> Vec<u64>;
+    /// How many times the last count found a candidate in the transactions
+    fn occurrences(&self) -> u64 {
+        0
+    }
+    /// Whether the passes after the last count use the transformed database,
+    /// given the occurrences of every rank.
+    /// Only asked for on rank 0, so every rank switches in the same pass.
+    fn switch(&self, occurrences: u64) -> bool {
+        let _ = occurrences;
+        false
+    }
     fn add(&mut self, v: &[u64]);
     fn count_2(&mut self, prev: &[usize]) -> Vec<u64>;
-    fn frequent(&mut self, sup: u64) -> TrieSet;
+    /// The frequent candidates, which also get written to out with their supports
+    fn frequent(&mut self, sup: u64, out: &mut impl Write) -> TrieSet;
+}
+
+/// How long rank 0 sleeps between polls for the replies of the other ranks
+const POLL: Duration = Duration::from_micros(50);
+
+/// Whether this rank may run threads while its main thread makes the MPI calls,
+/// which needs MPI to be started with at least Threading::Funneled
+pub fn threads_allowed() -> bool {
+    environment::threading_support() >= Threading::Funneled
+}
+
+/// threads if threads_allowed, otherwise 1, which rank 0 warns about
+pub(crate) fn rank_threads(threads: usize, uni: &Universe) -> usize {
+    if threads <= 1 || threads_allowed() {
+        return threads;
+    }
+    if uni.world().rank() == 0 {
+        eprintln!(
+            "MPI does not allow threads, so each rank counts with 1 thread instead of {threads}"
+        );
+    }
+    1
+}
+
+/// Sends msg to every other rank without blocking, then runs count on another thread,
+/// while the counts that the other ranks send back get polled for and merged as they arrive.
+/// Without threads_allowed, count runs first and the counts get merged after it.
+/// Returns the output of count, how long count took,
+/// and how long the messages took until the last one was merged.
+/// merge also gets the occurrences of each rank.
+/// Returns the first error instead if a rank sends back anything else than its counts,
+/// once every rank has replied.
+fn overlap<R: Send>(
+    uni: &Universe,
+    msg: &Message,
+    count: impl FnOnce() -> R + Send,
+    mut merge: impl FnMut(&[u64], u64),
+) -> Result<(R, Duration, Duration), HelperError> {
+    let world = uni.world();
+    let start = Instant::now();
+    let msg = msg.to_bytes();
+    let timed = || {
+        let start = Instant::now();
+        let r = count();
+        (r, start.elapsed())
+    };
+    mpi::request::scope(|scope| {
+        let sent: Vec<_> = (1..world.size())
+            .map(|i| world.process_at_rank(i).immediate_send(scope, &msg[..]))
+            .collect();
+        let ((r, compute), (failed, communication)) = if threads_allowed() {
+            thread::scope(|s| {
+                let counting = s.spawn(timed);
+                let poll = || loop {
+                    match Message::try_receive(&world.any_process()) {
+                        Some(reply) => break reply,
+                        None => thread::sleep(POLL),
+                    }
+                };
+                let gathered = gather(world.size() - 1, start, poll, &mut merge);
+                (counting.join().unwrap(), gathered)
+            })
+        } else {
+            let counted = timed();
+            let receive = || Message::receive(&world.any_process());
+            (
+                counted,
+                gather(world.size() - 1, start, receive, &mut merge),
+            )
+        };
+        for r in sent {
+            r.wait();
+        }
+        match failed {
+            Some(e) => Err(e),
+            None => Ok((r, compute, communication)),
+        }
+    })
+}
+
+/// Merges the replies that receive returns until replies of them have arrived.
+/// Returns the first error of a rank, and how long it took since start.
+fn gather(
+    replies: i32,
+    start: Instant,
+    mut receive: impl FnMut() -> (Result<Message, ProtocolError>, Status),
+    merge: &mut impl FnMut(&[u64], u64),
+) -> (Option<HelperError>, Duration) {
+    let mut failed = None;
+    for _ in 0..replies {
+        let error = match receive() {
+            (
+                Ok(Message::Counts {
+                    counts,
+                    occurrences,
+                }),
+                _,
+            ) => {
+                merge(&counts, occurrences);
+                continue;
+            }
+            (Ok(Message::Error(e)), _) => HelperError::Failed(e),
+            (Ok(_), status) => HelperError::Unexpected(status.source_rank()),
+            (Err(e), status) => HelperError::Invalid(status.source_rank(), e),
+        };
+        failed.get_or_insert(error);
+    }
+    (failed, start.elapsed())
 }
 
 pub(crate) struct MainRunner<'a, T: Write, U: ParallelCounting> {
     sup: u64,
-    writer: &'a mut T,
+    writer: CountingWriter<'a, T>,
     uni: &'a Universe,
     counter: U,
+    checkpoints: Option<Checkpoints>,
+    resumed: Option<Checkpoint>,
 }
 
-impl<'a, T: Write, U: ParallelCounting> MainRunner<'a, T, U> {
+impl<'a, T: Write, U: ParallelCounting + Send> MainRunner<'a, T, U> {
     pub fn new(sup: u64, writer: &'a mut T, uni: &'a Universe, counter: U) -> Self {
         Self {
             sup,
-            writer,
+            writer: CountingWriter::new(writer, 0),
             uni,
             counter,
+            checkpoints: None,
+            resumed: None,
         }
     }
+    /// Saves a checkpoint after every pass from 2 on
+    pub fn with_checkpoints(mut self, checkpoints: Option<Checkpoints>) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
+    /// Continues after the pass of resumed, if given
+    pub fn with_resume(mut self, resumed: Option<Checkpoint>) -> Self {
+        if let Some(checkpoint) = &resumed {
+            self.writer = CountingWriter::new(self.writer.into_inner(), checkpoint.written);
+        }
+        self.resumed = resumed;
+        self
+    }
     fn end(&mut self) {
         for i in 1..self.uni.world().size() {
-            self.uni.world().process_at_rank(i).send(&[u64::MAX]);
+            Message::Terminate.send(&self.uni.world().process_at_rank(i));
         }
     }
-    fn pass_two(&mut self, p1: &[usize]) -> TrieSet {
-        let p1set: Vec<u64> = p1.iter().map(|&n| n as u64).collect();
-        for i in 1..self.uni.world().size() {
-            self.uni.world().process_at_rank(i).send(&p1set);
+    fn save(&mut self, pass: usize, frequent: &mut TrieSet) {
+        if let Some(c) = &self.checkpoints {
+            c.save(pass, self.sup, frequent, &mut self.writer);
         }
+    }
+    fn pass_two(&mut self, p1: &[usize]) -> Result<TrieSet, HelperError> {
         let mut combined = AprioriP2Counter::new(p1);
-        combined.add_from_vec(&self.counter.count_2(p1));
-        for _ in 1..self.uni.world().size() {
-            let (v, _) = self.uni.world().any_process().receive_vec();
-            combined.add_from_vec(&v);
-        }
-        combined.to_frequent_new(self.sup)
+        let counter = &mut self.counter;
+        let (own, compute, communication) = overlap(
+            self.uni,
+            &Message::Items(p1.to_vec()),
+            || counter.count_2(p1),
+            |v, _| combined.add_from_vec(v),
+        )?;
+        combined.add_from_vec(&own);
+        println!("2 compute {compute:?} communication {communication:?}");
+        Ok(combined.write_frequent(self.sup, &mut self.writer))
     }
-    pub fn run(&mut self, p1: Vec<usize>) {
-        if p1.is_empty() {
-            self.end();
-            return;
-        }
-        let prev_time = Instant::now();
-        let mut p = self.pass_two(&p1);
-        p.for_each(|v| self.writer.write_set(v));
-        println!("2 {:?}", prev_time.elapsed());
+    /// Counts every pass after the first.
+    /// If a helper fails, every rank gets terminated and its error is returned.
+    pub fn run(&mut self, p1: Vec<usize>) -> Result<(), HelperError> {
+        let passes = self.passes(p1);
+        self.end();
+        passes
+    }
+    fn passes(&mut self, p1: Vec<usize>) -> Result<(), HelperError> {
+        let (mut p, start) = match self.resumed.take() {
+            Some(checkpoint) => (checkpoint.frequent, checkpoint.pass + 1),
+            None => {
+                if p1.is_empty() {
+                    return Ok(());
+                }
+                let prev_time = Instant::now();
+                let mut p = self.pass_two(&p1)?;
+                println!("2 {:?}", prev_time.elapsed());
+                self.save(2, &mut p);
+                (p, 3)
+            }
+        };
         if p.is_empty() {
-            self.end();
-            return;
+            return Ok(());
         }
-        for i in 3.. {
+        // Nothing has been counted before the first pass
+        let mut tid = self.counter.switch(u64::MAX);
+        for i in start.. {
             let prev_time = Instant::now();
-            let converted = p.to_vec();
-            for i in 1..self.uni.world().size() {
-                self.uni.world().process_at_rank(i).send(&converted);
+            let candidates = Message::Candidates {
+                n: i,
+                set: p.to_vec(),
+                tid,
+            };
+            // The counts of the other ranks have their own layouts, so they are merged in a trie
+            let mut incoming = TrieCounter::new();
+            let mut occurrences = 0u64;
+            let (counter, prev) = (&mut self.counter, &p);
+            let (_, compute, communication) = overlap(
+                self.uni,
+                &candidates,
+                || counter.count(prev, i, tid),
+                |v, o| {
+                    incoming.add_from_vec(v);
+                    occurrences = occurrences.saturating_add(o);
+                },
+            )?;
+            if self.uni.world().size() > 1 {
+                self.counter.add(&incoming.to_vec());
             }
-            self.counter.count(&p, i);
-            for _ in 1..self.uni.world().size() {
-                let (v, _) = self.uni.world().any_process().receive_vec();
-                self.counter.add(&v);
-            }
-            p = self.counter.frequent(self.sup);
+            occurrences = occurrences.saturating_add(self.counter.occurrences());
+            tid = tid || self.counter.switch(occurrences);
+            println!("{i} compute {compute:?} communication {communication:?}");
+            p = self.counter.frequent(self.sup, &mut self.writer);
             println!("{i} {:?}", prev_time.elapsed());
+            self.save(i, &mut p);
             if p.is_empty() {
                 break;
             }
-            p.for_each(|v| {
-                self.writer.write_set(v);
-            });
         }
-        self.end();
+        Ok(())
     }
-    pub fn preprocess(&mut self, data: &TransactionSet) -> Vec<usize> {
-        let p = apriori_pass_one(data, self.sup);
-        p.iter().for_each(|&n| self.writer.write_set(&[n]));
-        p
+    /// Writes the frequent items from pass_one and returns them.
+    /// A resumed run already wrote them.
+    pub fn preprocess(&mut self, p1: Vec<(usize, u64)>) -> Vec<usize> {
+        let write = self.resumed.is_none();
+        p1.into_iter()
+            .map(|(n, count)| {
+                if write {
+                    self.writer.write_set_with_support(&[n], count);
+                }
+                n
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use apriori::transaction_set::TransactionSet;
+
+    use super::PartitionStrategy;
+
+    #[test]
+    fn test_ranges() {
+        let set = TransactionSet::new(
+            vec![
+                vec![0, 1, 2, 3, 4, 5],
+                vec![0],
+                vec![1],
+                vec![2],
+                vec![3],
+                vec![4],
+                vec![5],
+                vec![],
+            ],
+            6,
+        );
+        assert_eq!(
+            PartitionStrategy::EqualCount.ranges(&set, 2),
+            vec![0..4, 4..8]
+        );
+        // The first transaction weighs 7 of 20
+        assert_eq!(
+            PartitionStrategy::EqualItems.ranges(&set, 2),
+            vec![0..3, 3..8]
+        );
+        // The first transaction has 42 subsets of at most 3 items, the others 2 or 1
+        assert_eq!(
+            PartitionStrategy::CostModel.ranges(&set, 2),
+            vec![0..1, 1..8]
+        );
+        assert_eq!(PartitionStrategy::CostModel.weight(&[0, 1, 2, 3, 4, 5]), 42);
+        // 1 + 2^22 + (2^22 choose 2) + (2^22 choose 3), whose products overflow u64
+        let long: Vec<usize> = (0..1 << 22).collect();
+        assert_eq!(
+            PartitionStrategy::CostModel.weight(&long),
+            12_297_829_382_476_529_665
+        );
+        let ranges = PartitionStrategy::EqualCount.ranges(&set, 3);
+        assert_eq!(ranges, vec![0..2, 2..5, 5..8]);
+        let ranges = PartitionStrategy::EqualItems.ranges(&set, 20);
+        assert_eq!(ranges.len(), 20);
+        assert_eq!(ranges.last().unwrap().end, set.len());
+        assert_eq!(ranges.iter().map(|r| r.len()).sum::<usize>(), set.len());
     }
 }
@@ -0,0 +1,214 @@
+use std::{convert::Infallible, time::Instant};
+
+use apriori::{
+    fp_growth::FPTree,
+    start::Write,
+    transaction_set::{Support, TransactionSet},
+};
+use mpi::{environment::Universe, traits::Communicator};
+use parallel::traits::ParallelRun;
+
+use crate::{
+    allreduce::{exchange, pass_one},
+    candidate_distribution::{Found, write_found},
+    main_thread::{Input, PartitionStrategy, global_support},
+};
+
+/// Parallel FP-Growth by Li et al.
+/// The frequent items are split into one group per rank.
+/// Each rank sends every transaction, cut after its last item of a group, to the rank of that group,
+/// then mines the itemsets ending in its own items from an FP-Tree of what it received.
+/// The itemsets are gathered on rank 0, which writes them.
+pub struct ParallelFPGrowth<'a, T: Write> {
+    data: Input<'a>,
+    sup: Support,
+    writer: &'a mut T,
+}
+
+impl<'a, T: Write> ParallelFPGrowth<'a, T> {
+    pub fn new(data: &'a TransactionSet, sup: impl Into<Support>, writer: &'a mut T) -> Self {
+        Self {
+            data: Input::Whole(data),
+            sup: sup.into(),
+            writer,
+        }
+    }
+    /// Runs on local, which is only the partition of this rank, like from load_partition
+    pub fn from_partition(
+        local: TransactionSet,
+        sup: impl Into<Support>,
+        writer: &'a mut T,
+    ) -> Self {
+        Self {
+            data: Input::Partition(local),
+            sup: sup.into(),
+            writer,
+        }
+    }
+}
+
+/// The group of every item in flist, which is sorted from the most to the least frequent.
+/// The conditional tree of the item at position i can hold the i items before it,
+/// so the items go from the last to the least loaded of size groups by support times position.
+/// Every rank gets the same groups as they only depend on flist.
+fn assign_groups(flist: &[(usize, u64)], num_items: usize, size: usize) -> Vec<usize> {
+    let mut groups = vec![usize::MAX; num_items];
+    let mut loads = vec![0u64; size];
+    for (i, &(item, count)) in flist.iter().enumerate().rev() {
+        let (group, _) = loads
+            .iter()
+            .enumerate()
+            .min_by_key(|(g, load)| (**load, *g))
+            .unwrap();
+        loads[group] += count * (i as u64 + 1);
+        groups[item] = group;
+    }
+    groups
+}
+
+/// The transactions of local for each group, each as its length followed by its items.
+/// A transaction goes to a group cut after its last item of the group in the order of flist.
+fn group_transactions(
+    local: &TransactionSet,
+    flist: &[(usize, u64)],
+    groups: &[usize],
+    size: usize,
+) -> Vec<Vec<u64>> {
+    let mut position = vec![usize::MAX; local.num_items];
+    for (i, &(item, _)) in flist.iter().enumerate() {
+        position[item] = i;
+    }
+    let mut out = vec![Vec::new(); size];
+    let mut sorted: Vec<usize> = Vec::new();
+    let mut sent = vec![false; size];
+    for d in local.iter() {
+        sorted.clear();
+        sorted.extend(d.iter().copied().filter(|&n| position[n] != usize::MAX));
+        sorted.sort_by_key(|&n| position[n]);
+        sent.iter_mut().for_each(|s| *s = false);
+        for j in (0..sorted.len()).rev() {
+            let group = groups[sorted[j]];
+            if sent[group] {
+                continue;
+            }
+            sent[group] = true;
+            out[group].push(j as u64 + 1);
+            out[group].extend(sorted[..=j].iter().map(|&n| n as u64));
+        }
+    }
+    out
+}
+
+/// Reads the transactions of group_transactions
+fn decode_transactions(v: &[u64]) -> Vec<Vec<usize>> {
+    let mut transactions = Vec::new();
+    let mut i = 0;
+    while i < v.len() {
+        let len = v[i] as usize;
+        transactions.push(
+            v[(i + 1)..(i + 1 + len)]
+                .iter()
+                .map(|&n| n as usize)
+                .collect(),
+        );
+        i += len + 1;
+    }
+    transactions
+}
+
+impl<T: Write> ParallelRun for ParallelFPGrowth<'_, T> {
+    type Error = Infallible;
+
+    fn run(mut self, universe: &Universe) -> Result<(), Self::Error> {
+        let world = universe.world();
+        let (rank, size) = (world.rank() as usize, world.size() as usize);
+        let local = self.data.local(PartitionStrategy::default(), universe);
+        let sup = global_support(self.sup, &local, universe);
+
+        let prev_time = Instant::now();
+        // Items that never occur have no tree to mine, even if sup is 0
+        let mut flist: Vec<(usize, u64)> = pass_one(&local, sup, universe)
+            .into_iter()
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        // Most frequent first, with ties broken by the item id, like FPTree
+        flist.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let groups = assign_groups(&flist, local.num_items, size);
+        let received = exchange(group_transactions(&local, &flist, &groups, size), universe);
+        if rank == 0 {
+            println!("Shuffle {:?}", prev_time.elapsed());
+        }
+
+        let prev_time = Instant::now();
+        let transactions = decode_transactions(&received);
+        let tree = FPTree::with_order(
+            transactions.iter().map(|t| (t.as_slice(), 1)),
+            flist.iter().map(|&(item, _)| item).collect(),
+        );
+        let mut found = Found::default();
+        tree.mine_items(sup, |item| groups[item] == rank, &mut found);
+        write_found(&found.0, self.writer, universe);
+        if rank == 0 {
+            println!("Mine {:?}", prev_time.elapsed());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use apriori::{
+        fp_growth::{FPGrowth, FPTree},
+        start::{Apriori, FrequentWriter},
+        transaction_set::TransactionSet,
+    };
+
+    use super::{assign_groups, decode_transactions, group_transactions};
+
+    #[test]
+    fn test_group_transactions() {
+        let flist = vec![(2, 5), (1, 4), (3, 3), (0, 2)];
+        let groups = assign_groups(&flist, 5, 2);
+        // 0 weighs 8, then 3 weighs 9, 1 weighs 8 and 2 weighs 5
+        assert_eq!(groups, vec![0, 0, 1, 1, usize::MAX]);
+        let local = TransactionSet::new(vec![vec![0, 1, 2, 3, 4], vec![1, 3], vec![4]], 5);
+        let out = group_transactions(&local, &flist, &groups, 2);
+        // The first transaction is 2 1 3 0 in the order of flist
+        assert_eq!(out[0], vec![4, 2, 1, 3, 0, 1, 1]);
+        assert_eq!(out[1], vec![3, 2, 1, 3, 2, 1, 3]);
+    }
+    #[test]
+    fn test_groups_mine_everything() {
+        let set = TransactionSet::new(
+            vec![
+                vec![0, 1, 2],
+                vec![0, 1],
+                vec![1, 2, 3],
+                vec![0, 1, 2, 3],
+                vec![3],
+                vec![0, 2, 3],
+            ],
+            4,
+        );
+        let mut expected = FrequentWriter::<HashSet<Vec<usize>>>::new();
+        FPGrowth::new(&set, 2).run(&mut expected);
+        let expected = expected.into_inner();
+        let flist = vec![(0, 4), (1, 4), (2, 4), (3, 4)];
+        for size in 1..=3 {
+            let groups = assign_groups(&flist, 4, size);
+            let out = group_transactions(&set, &flist, &groups, size);
+            let mut s = FrequentWriter::<HashSet<Vec<usize>>>::new();
+            for (rank, received) in out.iter().enumerate() {
+                let transactions = decode_transactions(received);
+                let tree = FPTree::with_order(
+                    transactions.iter().map(|t| (t.as_slice(), 1)),
+                    flist.iter().map(|&(item, _)| item).collect(),
+                );
+                tree.mine_items(2, |item| groups[item] == rank, &mut s);
+            }
+            assert_eq!(s.into_inner(), expected);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt::Display;
+
+use apriori::trie::TrieSet;
+use mpi::{
+    environment::Universe,
+    point_to_point::{Destination, MatchedReceiveVec, Source, Status},
+    traits::Communicator,
+};
+use parallel::encoding::{DecodeError, decode, encode};
+
+use crate::main_thread::ParallelCounting;
+
+/// A message between rank 0 and the helpers of the Count Distribution runners
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// The frequent items of pass 1, whose pairs get counted in pass 2
+    Items(Vec<usize>),
+    /// The frequent itemsets of pass n - 1 from TrieSet::to_vec,
+    /// which get joined into the candidates of pass n.
+    /// They get counted with the transformed database of AprioriTID if tid.
+    Candidates { n: usize, set: Vec<u64>, tid: bool },
+    /// The counts of a helper from Convertable::to_vec,
+    /// and how many times it found a candidate in its transactions
+    Counts { counts: Vec<u64>, occurrences: u64 },
+    /// There are no more passes
+    Terminate,
+    /// The sender failed for this reason
+    Error(String),
+}
+
+/// A message that could not be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    Empty,
+    UnknownTag(u8),
+    /// A candidates or counts message without the values in front of its payload
+    MissingHeader,
+    /// The payload ends in the middle of a value
+    Truncated,
+    /// A value of the payload is longer than a u64
+    TooLong,
+    /// Candidates whose set is not the itemsets of the pass before theirs
+    InvalidCandidates,
+    InvalidError,
+}
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::Empty => write!(f, "empty message"),
+            ProtocolError::UnknownTag(tag) => write!(f, "unknown message tag {tag}"),
+            ProtocolError::MissingHeader => write!(f, "message without its header"),
+            ProtocolError::Truncated => write!(f, "truncated message"),
+            ProtocolError::TooLong => write!(f, "message with a value longer than a u64"),
+            ProtocolError::InvalidCandidates => write!(f, "candidates that are not itemsets"),
+            ProtocolError::InvalidError => write!(f, "error message is not UTF-8"),
+        }
+    }
+}
+impl std::error::Error for ProtocolError {}
+impl From<DecodeError> for ProtocolError {
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::Truncated => ProtocolError::Truncated,
+            DecodeError::TooLong => ProtocolError::TooLong,
+        }
+    }
+}
+
+/// Why rank 0 stopped every rank, instead of getting back the counts of a helper
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HelperError {
+    /// A helper sent back this error
+    Failed(String),
+    /// The rank sent something else than its counts
+    Unexpected(i32),
+    /// The message of the rank could not be read
+    Invalid(i32, ProtocolError),
+}
+impl Display for HelperError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HelperError::Failed(e) => write!(f, "{e}"),
+            HelperError::Unexpected(rank) => write!(f, "rank {rank} did not send its counts"),
+            HelperError::Invalid(rank, e) => write!(f, "rank {rank}: {e}"),
+        }
+    }
+}
+impl std::error::Error for HelperError {}
+
+const ITEMS: u8 = 1;
+const CANDIDATES: u8 = 2;
+const COUNTS: u8 = 3;
+const TERMINATE: u8 = 4;
+const ERROR: u8 = 5;
+
+impl Message {
+    /// The tag of the message followed by its payload in the compact encoding
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (tag, payload) = match self {
+            Message::Items(items) => {
+                let items: Vec<u64> = items.iter().map(|&n| n as u64).collect();
+                (ITEMS, encode(&items))
+            }
+            Message::Candidates { n, set, tid } => {
+                let mut v = Vec::with_capacity(set.len() + 2);
+                v.push(*n as u64);
+                v.push(*tid as u64);
+                v.extend_from_slice(set);
+                (CANDIDATES, encode(&v))
+            }
+            Message::Counts {
+                counts,
+                occurrences,
+            } => {
+                let mut v = Vec::with_capacity(counts.len() + 1);
+                v.push(*occurrences);
+                v.extend_from_slice(counts);
+                (COUNTS, encode(&v))
+            }
+            Message::Terminate => (TERMINATE, Vec::new()),
+            Message::Error(e) => (ERROR, e.as_bytes().to_vec()),
+        };
+        let mut bytes = Vec::with_capacity(payload.len() + 1);
+        bytes.push(tag);
+        bytes.extend(payload);
+        bytes
+    }
+    /// Reads the output of to_bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let (&tag, payload) = bytes.split_first().ok_or(ProtocolError::Empty)?;
+        match tag {
+            ITEMS => Ok(Message::Items(
+                decode(payload)?.into_iter().map(|n| n as usize).collect(),
+            )),
+            CANDIDATES => {
+                let v = decode(payload)?;
+                let [n, tid, set @ ..] = &v[..] else {
+                    return Err(ProtocolError::MissingHeader);
+                };
+                Ok(Message::Candidates {
+                    n: *n as usize,
+                    set: set.to_vec(),
+                    tid: *tid != 0,
+                })
+            }
+            COUNTS => {
+                let v = decode(payload)?;
+                let (&occurrences, counts) = v.split_first().ok_or(ProtocolError::MissingHeader)?;
+                Ok(Message::Counts {
+                    counts: counts.to_vec(),
+                    occurrences,
+                })
+            }
+            TERMINATE => Ok(Message::Terminate),
+            ERROR => String::from_utf8(payload.to_vec())
+                .map(Message::Error)
+                .map_err(|_| ProtocolError::InvalidError),
+            _ => Err(ProtocolError::UnknownTag(tag)),
+        }
+    }
+    /// Sends the message to process, blocking until it can be reused
+    pub fn send(&self, process: &impl Destination) {
+        process.send(&self.to_bytes()[..]);
+    }
+    /// Receives the next message from process
+    pub fn receive(process: &impl Source) -> (Result<Self, ProtocolError>, Status) {
+        let (bytes, status) = process.receive_vec::<u8>();
+        (Self::from_bytes(&bytes), status)
+    }
+    /// Receives the next message from process if one has arrived, without blocking
+    pub fn try_receive(process: &impl Source) -> Option<(Result<Self, ProtocolError>, Status)> {
+        let (bytes, status) = process
+            .immediate_matched_probe()?
+            .matched_receive_vec::<u8>();
+        Some((Self::from_bytes(&bytes), status))
+    }
+}
+
+/// The itemsets of set from TrieSet::to_vec, which have to be n - 1 items long
+fn candidates(n: usize, set: &[u64]) -> Result<TrieSet, ProtocolError> {
+    match (set.first(), TrieSet::from_vec(set)) {
+        (Some(&depth), Some(trie)) if depth + 1 == n as u64 => Ok(trie),
+        _ => Err(ProtocolError::InvalidCandidates),
+    }
+}
+
+/// Answers the messages of rank 0 with the counts of counter until it terminates.
+/// Anything else than items, candidates or terminate gets an error back,
+/// after which rank 0 still has to send terminate.
+pub(crate) fn serve(counter: &mut impl ParallelCounting, uni: &Universe) {
+    let world = uni.world();
+    let root = world.process_at_rank(0);
+    let (mut received, _) = Message::receive(&root);
+    loop {
+        let reply = match received {
+            Ok(Message::Items(items)) => Message::Counts {
+                counts: counter.count_2(&items),
+                occurrences: 0,
+            },
+            Ok(Message::Candidates { n, set, tid }) => match candidates(n, &set) {
+                Ok(trie) => Message::Counts {
+                    counts: counter.count(&trie, n, tid),
+                    occurrences: counter.occurrences(),
+                },
+                Err(e) => Message::Error(format!("rank {}: {e}", world.rank())),
+            },
+            Ok(Message::Terminate) => break,
+            Ok(_) => Message::Error(format!(
+                "rank {} expected items, candidates or terminate",
+                world.rank()
+            )),
+            Err(e) => Message::Error(format!("rank {}: {e}", world.rank())),
+        };
+        let reply = reply.to_bytes();
+        // The counts go back while this rank already waits for the next candidates
+        received = mpi::request::scope(|scope| {
+            let sent = root.immediate_send(scope, &reply[..]);
+            let (next, _) = Message::receive(&root);
+            sent.wait();
+            next
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use apriori::{storage::AprioriFrequent, trie::TrieSet};
+    use parallel::traits::Convertable;
+
+    use super::{HelperError, Message, ProtocolError, candidates};
+
+    #[test]
+    fn test_round_trip() {
+        let messages = [
+            Message::Items(vec![0, 3, 200]),
+            Message::Items(vec![]),
+            Message::Candidates {
+                n: 3,
+                set: vec![2, 1, 0, 2, 1, 1],
+                tid: false,
+            },
+            // Candidates that start with u64::MAX are still not a Terminate
+            Message::Candidates {
+                n: 4,
+                set: vec![u64::MAX],
+                tid: true,
+            },
+            Message::Counts {
+                counts: vec![0, 0, 5, 1 << 40],
+                occurrences: 12,
+            },
+            Message::Counts {
+                counts: vec![],
+                occurrences: 0,
+            },
+            Message::Terminate,
+            Message::Error("rank 2 ran out of memory".to_string()),
+        ];
+        for m in messages {
+            assert_eq!(Message::from_bytes(&m.to_bytes()), Ok(m));
+        }
+    }
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Message::from_bytes(&[]), Err(ProtocolError::Empty));
+        assert_eq!(
+            Message::from_bytes(&[9, 1]),
+            Err(ProtocolError::UnknownTag(9))
+        );
+        assert_eq!(Message::from_bytes(&[2]), Err(ProtocolError::MissingHeader));
+        assert_eq!(
+            Message::from_bytes(&[2, 3]),
+            Err(ProtocolError::MissingHeader)
+        );
+        assert_eq!(Message::from_bytes(&[3]), Err(ProtocolError::MissingHeader));
+        assert_eq!(
+            Message::from_bytes(&[3, 0x80]),
+            Err(ProtocolError::Truncated)
+        );
+        assert_eq!(
+            Message::from_bytes(&[
+                1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1
+            ]),
+            Err(ProtocolError::TooLong)
+        );
+        assert_eq!(
+            Message::from_bytes(&[5, 0xff]),
+            Err(ProtocolError::InvalidError)
+        );
+        assert_eq!(Message::Terminate.to_bytes(), vec![4]);
+    }
+    #[test]
+    fn test_candidates() {
+        let mut set = TrieSet::new();
+        set.insert(&[1, 2]);
+        set.insert(&[1, 3]);
+        let v = set.to_vec();
+        assert_eq!(candidates(3, &v).map(|t| t.len()), Ok(2));
+        assert_eq!(
+            candidates(4, &v).err(),
+            Some(ProtocolError::InvalidCandidates)
+        );
+        assert_eq!(
+            candidates(3, &[]).err(),
+            Some(ProtocolError::InvalidCandidates)
+        );
+        assert_eq!(
+            candidates(3, &v[..v.len() - 1]).err(),
+            Some(ProtocolError::InvalidCandidates)
+        );
+    }
+    #[test]
+    fn test_helper_error() {
+        assert_eq!(
+            HelperError::Invalid(2, ProtocolError::Truncated).to_string(),
+            "rank 2: truncated message"
+        );
+        assert_eq!(
+            HelperError::Unexpected(1).to_string(),
+            "rank 1 did not send its counts"
+        );
+    }
+}
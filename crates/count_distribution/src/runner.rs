@@ -1,92 +1,155 @@
 use std::time::Instant;
 
 use apriori::{
-    apriori::apriori_pass_two_counter,
+    apriori::{apriori_pass_three_counter, apriori_pass_two_counter},
     array2d::AprioriP2Counter,
+    checkpoint::{Checkpoint, Checkpoints},
     start::Write,
-    storage::{AprioriCounter, AprioriCounting, AprioriFrequent},
-    transaction_set::TransactionSet,
+    storage::{AprioriCounter, AprioriFrequent},
+    threaded::{count_parallel, default_threads, split},
+    transaction_set::{Support, TransactionSet},
     trie::{TrieCounter, TrieSet},
 };
-use mpi::{
-    environment::Universe,
-    traits::{Communicator, Destination, Source},
-};
+use mpi::{environment::Universe, traits::Communicator};
 use parallel::traits::{Convertable, ParallelRun};
 
-use crate::main_thread::{MainRunner, ParallelCounting};
+use crate::{
+    allreduce::pass_one,
+    main_thread::{
+        Input, MainRunner, ParallelCounting, PartitionStrategy, global_support, rank_threads,
+    },
+    protocol::{HelperError, serve},
+};
 
 pub struct CountDistribution<'a, T: Write> {
-    data: &'a TransactionSet,
-    sup: u64,
+    data: Input<'a>,
+    sup: Support,
+    threads: usize,
+    strategy: PartitionStrategy,
+    checkpoints: Option<Checkpoints>,
+    resume: Option<Checkpoint>,
     writer: &'a mut T,
 }
 
 impl<'a, T: Write> CountDistribution<'a, T> {
-    pub fn new(data: &'a TransactionSet, sup: u64, writer: &'a mut T) -> Self {
-        Self { data, sup, writer }
+    pub fn new(data: &'a TransactionSet, sup: impl Into<Support>, writer: &'a mut T) -> Self {
+        Self {
+            data: Input::Whole(data),
+            sup: sup.into(),
+            threads: 1,
+            strategy: PartitionStrategy::default(),
+            checkpoints: None,
+            resume: None,
+            writer,
+        }
+    }
+    /// Runs on local, which is only the partition of this rank, like from load_partition
+    pub fn from_partition(
+        local: TransactionSet,
+        sup: impl Into<Support>,
+        writer: &'a mut T,
+    ) -> Self {
+        Self {
+            data: Input::Partition(local),
+            sup: sup.into(),
+            threads: 1,
+            strategy: PartitionStrategy::default(),
+            checkpoints: None,
+            resume: None,
+            writer,
+        }
+    }
+    /// Sets the number of threads that count the partition of each rank,
+    /// or every available core if threads is 0.
+    /// Each rank counts with 1 thread if MPI was started below Threading::Funneled.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = if threads == 0 {
+            default_threads()
+        } else {
+            threads
+        };
+        self
+    }
+    /// Sets how the whole database gets split between the ranks.
+    /// Does nothing if the runner was made from a partition.
+    pub fn with_partition(mut self, strategy: PartitionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+    /// Makes rank 0 save a checkpoint after every pass from 2 on
+    pub fn with_checkpoints(mut self, checkpoints: Checkpoints) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+    /// Makes every rank continue after the pass of checkpoint,
+    /// which has to be made with the same support.
+    /// Only rank 0 needs it, and its writer already has the itemsets written up to it.
+    pub fn with_resume(mut self, checkpoint: Checkpoint) -> Self {
+        self.resume = Some(checkpoint);
+        self
     }
 }
 
 impl<T: Write> ParallelRun for CountDistribution<'_, T> {
-    fn run(self, universe: &Universe) {
-        let size = universe.world().size();
-        assert!(size > 1, "Rank must be at least 2");
+    type Error = HelperError;
+
+    fn run(mut self, universe: &Universe) -> Result<(), Self::Error> {
         let rank = universe.world().rank();
+        let local = self.data.local(self.strategy, universe);
+        let threads = rank_threads(self.threads, universe);
+        let sup = global_support(self.sup, &local, universe);
+        let temp = Instant::now();
+        let p1 = pass_one(&local, sup, universe);
+        // With a single rank, rank 0 counts the whole database through its own MainHelper
         if rank == 0 {
-            let mut a = MainRunner::new(
-                self.sup,
-                self.writer,
-                universe,
-                MainHelper::new(self.data, universe),
-            );
-            let temp = Instant::now();
-            let b = a.preprocess(self.data);
             println!("Preprocess {:?}", temp.elapsed());
-            a.run(b);
+            let mut a =
+                MainRunner::new(sup, self.writer, universe, MainHelper::new(local, threads))
+                    .with_checkpoints(self.checkpoints)
+                    .with_resume(self.resume);
+            let b = a.preprocess(p1);
+            a.run(b)
         } else {
-            let mut a = HelperRunner::new(self.data, universe);
+            let mut a = HelperRunner::new(local, universe, threads);
             a.run();
+            Ok(())
         }
     }
 }
 
 struct MainHelper {
+    /// The partition of this rank, split between its threads
     data: TransactionSet,
+    threads: usize,
     counter: TrieCounter,
 }
 impl MainHelper {
-    pub fn new(data: &TransactionSet, uni: &Universe) -> Self {
-        let world = uni.world();
-        let count = data.len() / world.size() as usize;
-        let thread = world.rank() as usize;
-        let slice = if world.rank() == world.size() - 1 {
-            &data.transactions[(count * thread)..data.len()]
-        } else {
-            &data[(count * thread)..(count * (thread + 1))]
-        };
-        let data = TransactionSet::new(slice.to_vec(), data.num_items);
-
+    pub fn new(data: TransactionSet, threads: usize) -> Self {
         Self {
             data,
+            threads,
             counter: TrieCounter::new(),
         }
     }
 }
 impl ParallelCounting for MainHelper {
-    fn count(&mut self, set: &TrieSet, n: usize) -> Vec<u64> {
-        let mut counter: TrieCounter = set.join_new();
-        for d in self.data.iter() {
-            counter.count(d, n);
-        }
+    fn count(&mut self, set: &TrieSet, n: usize, _: bool) -> Vec<u64> {
+        let mut counter = count_parallel(
+            &split(&self.data, self.threads),
+            || set.join_new::<TrieCounter>(),
+            |part, counter| apriori_pass_three_counter(part, counter, n),
+        );
         let v = counter.to_vec();
         self.counter = counter;
         v
     }
 
     fn count_2(&mut self, prev: &[usize]) -> Vec<u64> {
-        let mut p2 = AprioriP2Counter::new(prev);
-        apriori_pass_two_counter(&self.data, &mut p2);
+        let mut p2 = count_parallel(
+            &split(&self.data, self.threads),
+            || AprioriP2Counter::new(prev),
+            apriori_pass_two_counter,
+        );
         p2.to_vec()
     }
 
@@ -94,8 +157,8 @@ impl ParallelCounting for MainHelper {
         self.counter.add_from_vec(v);
     }
 
-    fn frequent(&mut self, sup: u64) -> TrieSet {
-        self.counter.to_frequent_new(sup)
+    fn frequent(&mut self, sup: u64, out: &mut impl Write) -> TrieSet {
+        self.counter.write_frequent(sup, out)
     }
 }
 
@@ -105,27 +168,11 @@ struct HelperRunner<'a> {
 }
 
 impl<'a> HelperRunner<'a> {
-    pub fn new(data: &TransactionSet, uni: &'a Universe) -> Self {
-        let counter = MainHelper::new(data, uni);
+    pub fn new(data: TransactionSet, uni: &'a Universe, threads: usize) -> Self {
+        let counter = MainHelper::new(data, threads);
         Self { counter, uni }
     }
     fn run(&mut self) {
-        for n in 2.. {
-            let a: (Vec<u64>, mpi::point_to_point::Status) =
-                self.uni.world().process_at_rank(0).receive_vec();
-            if a.0[0] == u64::MAX {
-                break;
-            }
-            if n == 2 {
-                let a: Vec<_> = a.0.into_iter().map(|n| n as usize).collect();
-                let v = self.counter.count_2(&a);
-                self.uni.world().process_at_rank(0).send(&v);
-            } else {
-                let mut trie = TrieSet::new();
-                trie.add_from_vec(&a.0);
-                let v = self.counter.count(&trie, n);
-                self.uni.world().process_at_rank(0).send(&v);
-            }
-        }
+        serve(&mut self.counter, self.uni);
     }
 }
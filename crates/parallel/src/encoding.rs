@@ -0,0 +1,125 @@
+//! A compact encoding of the u64 vectors of Convertable.
+//! Every value is a LEB128 varint, and each run of zeros is a 0 byte followed by its length,
+//! since the first byte of a varint is only 0 for the value 0.
+
+use std::fmt::Display;
+
+/// Bytes that are not the output of encode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The bytes end in the middle of a varint
+    Truncated,
+    /// A varint with more than the 10 bytes of a u64
+    TooLong,
+}
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "truncated varint"),
+            DecodeError::TooLong => write!(f, "varint longer than a u64"),
+        }
+    }
+}
+impl std::error::Error for DecodeError {}
+
+/// Appends n as a LEB128 varint
+fn push_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+/// Reads the LEB128 varint at the start of the iterator
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Result<u64, DecodeError> {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let b = bytes.next().ok_or(DecodeError::Truncated)?;
+        // The 10th byte only has the highest bit of a u64 left
+        if shift == 63 && b > 1 {
+            return Err(DecodeError::TooLong);
+        }
+        n |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(DecodeError::TooLong)
+}
+
+/// Encodes v compactly
+pub fn encode(v: &[u64]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(v.len());
+    let mut i = 0;
+    while i < v.len() {
+        if v[i] == 0 {
+            let run = v[i..].iter().take_while(|&&n| n == 0).count();
+            out.push(0);
+            push_varint(&mut out, run as u64);
+            i += run;
+        } else {
+            push_varint(&mut out, v[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Decodes the output of encode
+pub fn decode(bytes: &[u8]) -> Result<Vec<u64>, DecodeError> {
+    let mut v = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter().copied().peekable();
+    while let Some(&b) = iter.peek() {
+        if b == 0 {
+            iter.next();
+            let run = read_varint(&mut iter)?;
+            v.extend(std::iter::repeat_n(0, run as usize));
+        } else {
+            v.push(read_varint(&mut iter)?);
+        }
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeError, decode, encode};
+
+    #[test]
+    fn test_round_trip() {
+        let cases: [&[u64]; 6] = [
+            &[],
+            &[0],
+            &[1, 2, 3],
+            &[0, 0, 0, 5, 0, 127, 128, 0, 0],
+            &[u64::MAX, 0, u64::MAX],
+            &[300, 16384, 1 << 40],
+        ];
+        for v in cases {
+            assert_eq!(decode(&encode(v)).unwrap(), v);
+        }
+    }
+    #[test]
+    fn test_truncated() {
+        assert_eq!(decode(&[0x80]), Err(DecodeError::Truncated));
+        assert_eq!(decode(&[1, 0]), Err(DecodeError::Truncated));
+    }
+    #[test]
+    fn test_too_long() {
+        assert_eq!(decode(&[0xff; 11]), Err(DecodeError::TooLong));
+        // 10 bytes, but more than 64 bits
+        let mut v = vec![0xff; 9];
+        v.push(2);
+        assert_eq!(decode(&v), Err(DecodeError::TooLong));
+        v[9] = 1;
+        assert_eq!(decode(&v), Ok(vec![u64::MAX]));
+    }
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(&[1, 127, 128]), vec![1, 127, 0x80, 1]);
+        // A run of zeros is 2 bytes instead of 8 bytes per zero
+        assert_eq!(encode(&[0; 1000]), vec![0, 0xe8, 7]);
+        assert_eq!(encode(&[u64::MAX]).len(), 10);
+    }
+}
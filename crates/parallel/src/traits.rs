@@ -1,9 +1,50 @@
 use mpi::environment::Universe;
 
+use crate::encoding::{decode, encode};
+
 pub trait Convertable {
     fn to_vec(&mut self) -> Vec<u64>;
     fn add_from_vec(&mut self, v: &[u64]);
+    /// to_vec in the compact encoding, for sending
+    fn to_bytes(&mut self) -> Vec<u8> {
+        encode(&self.to_vec())
+    }
+    /// add_from_vec for the output of to_bytes.
+    /// Panics if v is not the output of to_bytes.
+    fn add_from_bytes(&mut self, v: &[u8]) {
+        self.add_from_vec(&decode(v).expect("Invalid counts"));
+    }
 }
 pub trait ParallelRun {
-    fn run(self, universe: &Universe);
-}
\ No newline at end of file
+    /// Why a run stopped before its last pass
+    type Error;
+    /// Every rank has to call it, and the error only comes back on rank 0
+    fn run(self, universe: &Universe) -> Result<(), Self::Error>;
+}
+impl Convertable for Vec<u64> {
+    fn to_vec(&mut self) -> Vec<u64> {
+        self.clone()
+    }
+
+    fn add_from_vec(&mut self, v: &[u64]) {
+        assert_eq!(self.len(), v.len(), "The counters have different layouts");
+        self.iter_mut().zip(v).for_each(|(a, b)| *a += b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Convertable;
+
+    #[test]
+    fn test_add_from_vec() {
+        let mut v = vec![1u64, 2, 3];
+        v.add_from_vec(&[3, 2, 1]);
+        assert_eq!(v, vec![4, 4, 4]);
+    }
+    #[test]
+    #[should_panic]
+    fn test_add_from_vec_length() {
+        vec![1u64, 2, 3].add_from_vec(&[1, 2]);
+    }
+}
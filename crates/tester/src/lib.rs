@@ -1,2 +1,2 @@
 // #[cfg(test)]
-pub mod test_utils;
\ No newline at end of file
+pub mod test_utils;
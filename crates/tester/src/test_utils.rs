@@ -1,11 +1,17 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Read},
     path::Path,
 };
 
-use apriori::transaction_set::TransactionSet;
+use apriori::{
+    checkpoint::{Checkpoints, CountingWriter},
+    start::Write,
+    storage::AprioriFrequent,
+    transaction_set::TransactionSet,
+    trie::TrieSet,
+};
 pub const SOLVED: &str = "solve1.dat";
 pub const DATABASE: &str = "test1.dat";
 #[derive(Debug)]
@@ -22,13 +28,18 @@ impl Solved {
         Self { set }
     }
     pub fn from_file(file: File) -> Result<Self, FromFileError> {
+        Self::from_reader(file)
+    }
+    pub fn from_reader(reader: impl Read) -> Result<Self, FromFileError> {
         let mut this = Self::default();
-        for l in BufReader::new(file).lines() {
+        for l in BufReader::new(reader).lines() {
             if l.is_err() {
                 continue;
             }
             let l = l.unwrap();
-            let t: Vec<_> = l
+            // The support of the SPMF format follows the items after a #
+            let items = l.split('#').next().unwrap_or_default();
+            let t: Vec<_> = items
                 .split_whitespace()
                 .filter_map(|n| n.parse::<usize>().ok())
                 .collect();
@@ -55,7 +66,7 @@ pub fn test_generic_with_option<T: AsRef<Path>>(
     let solved = test_files.as_ref().join(SOLVED);
     assert!(database.exists(), "Database file does not exist");
     let data = File::open(database).unwrap();
-    let t = TransactionSet::from_dat(data);
+    let t = TransactionSet::from_dat(data).expect("Invalid database");
 
     let s = f(t, 10);
     assert!(solved.exists(), "Solved file does not exist");
@@ -68,3 +79,33 @@ pub fn test_generic_with_option<T: AsRef<Path>>(
     assert_eq!(s.set.len(), s2.set.len());
     assert_eq!(s, s2);
 }
+
+/// Collects every itemset with its support
+#[derive(Default)]
+pub struct SupportWriter(pub HashMap<Vec<usize>, u64>);
+impl Write for SupportWriter {
+    fn write_set_with_support(&mut self, v: &[usize], sup: u64) {
+        self.0.insert(v.to_vec(), sup);
+    }
+}
+
+/// Saves the checkpoint that a run finding the itemsets of full would make after pass,
+/// and returns the output written up to it
+pub fn checkpoint_after(
+    full: &HashMap<Vec<usize>, u64>,
+    pass: usize,
+    sup: u64,
+    checkpoints: &Checkpoints,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut writer = CountingWriter::new(&mut out, 0);
+    let mut frequent = TrieSet::new();
+    for (v, &count) in full.iter().filter(|(v, _)| v.len() <= pass) {
+        writer.write_set_with_support(v, count);
+        if v.len() == pass {
+            frequent.insert(v);
+        }
+    }
+    checkpoints.save(pass, sup, &mut frequent, &mut writer);
+    out
+}
@@ -2,23 +2,23 @@ use std::collections::HashSet;
 
 use apriori::start::FrequentWriter;
 use count_distribution::hybridrunner::CountDistributionHybrid;
-use mpi::traits::Communicator;
+use mpi::{Threading, traits::Communicator};
 use parallel::traits::ParallelRun;
 use tester::test_utils::test_generic_with_option;
 
 fn main() {
-    let universe = mpi::initialize();
-    let universe = universe.unwrap();
+    let (universe, _) = mpi::initialize_with_threading(Threading::Funneled).unwrap();
     let world = universe.world();
-    assert!(world.size() > 1);
-    test_generic_with_option("./test_files", |t, s| {
-        let mut writer: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
-        let a = CountDistributionHybrid::new(&t, s, &mut writer);
-        a.run(&universe);
-        if world.rank() == 0 {
-            Some(writer.into_inner().into())
-        } else {
-            None
-        }
-    });
+    for threads in [1, 3] {
+        test_generic_with_option("./test_files", |t, s| {
+            let mut writer: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+            let a = CountDistributionHybrid::new(&t, s, &mut writer).with_threads(threads);
+            a.run(&universe).unwrap();
+            if world.rank() == 0 {
+                Some(writer.into_inner().into())
+            } else {
+                None
+            }
+        });
+    }
 }
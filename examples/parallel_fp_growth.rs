@@ -0,0 +1,22 @@
+use std::collections::HashSet;
+
+use apriori::start::FrequentWriter;
+use count_distribution::pfp::ParallelFPGrowth;
+use mpi::{Threading, traits::Communicator};
+use parallel::traits::ParallelRun;
+use tester::test_utils::test_generic_with_option;
+
+fn main() {
+    let (universe, _) = mpi::initialize_with_threading(Threading::Funneled).unwrap();
+    let world = universe.world();
+    test_generic_with_option("./test_files", |t, s| {
+        let mut writer: FrequentWriter<HashSet<Vec<usize>>> = FrequentWriter::new();
+        let a = ParallelFPGrowth::new(&t, s, &mut writer);
+        a.run(&universe).unwrap();
+        if world.rank() == 0 {
+            Some(writer.into_inner().into())
+        } else {
+            None
+        }
+    });
+}
@@ -1,18 +1,34 @@
 use apriori::alone::AprioriTrie;
 use apriori::apriori::AprioriRunner;
+use apriori::checkpoint::{Checkpoint, Checkpoints};
+use apriori::closed::{ClosedRunner, ClosedWriter};
+use apriori::declat::DEclat;
+use apriori::eclat::Eclat;
+use apriori::fp_growth::FPGrowth;
+use apriori::maximal::{MaximalRunner, MaximalWriter};
+use apriori::rules::{Measure, Rule, RuleFilter, RuleWriter, WriteRule};
 use apriori::start::{Apriori, Write};
-use apriori::transaction_set::TransactionSet;
+use apriori::threaded::ThreadedCountDistribution;
+use apriori::transaction_set::{BadLines, DatError, Support, TransactionSet};
 use apriori_tid::hybrid::AprioriHybridRunner;
 use apriori_tid::tid::AprioriTIDRunner2;
 use clap::Parser;
 use clap::*;
-use count_distribution::hybridrunner::CountDistributionHybrid;
+use count_distribution::allreduce::CountDistributionAllReduce;
+use count_distribution::candidate_distribution::{CandidateDistribution, DEFAULT_SWITCH};
+use count_distribution::data_distribution::DataDistribution;
+use count_distribution::distributed_eclat::DistributedEclat;
+use count_distribution::hybridrunner::{CountDistributionHybrid, Switch};
+use count_distribution::main_thread::{PartitionStrategy, global_len, load_partition};
+use count_distribution::pfp::ParallelFPGrowth;
+use count_distribution::protocol::HelperError;
 use count_distribution::runner::CountDistribution;
+use mpi::Threading;
 use mpi::environment::{self, Universe};
-use mpi::traits::Communicator;
+use mpi::traits::{Communicator, Root};
 use parallel::traits::ParallelRun;
 use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write as IOWrite};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Seek, SeekFrom, Write as IOWrite};
 use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 use std::time::{Duration, Instant};
@@ -20,7 +36,8 @@ use std::time::{Duration, Instant};
 #[derive(Parser)]
 pub struct Args {
     file: PathBuf,
-    support_count: u64,
+    /// A count like 20, a fraction like 0.02 or a percentage like 2%
+    support_count: Support,
     algorithm: Algorithms,
     #[arg(short, long, default_value = "false")]
     time: bool,
@@ -28,6 +45,131 @@ pub struct Args {
     output: Option<PathBuf>,
     #[arg(long)]
     csv: Option<PathBuf>,
+    /// Only output the closed frequent itemsets
+    #[arg(long, default_value = "false")]
+    closed: bool,
+    /// Only output the maximal frequent itemsets
+    #[arg(long, default_value = "false", conflicts_with = "closed")]
+    maximal: bool,
+    /// Output the association rules with at least this confidence instead of the itemsets
+    #[arg(long, value_parser = parse_fraction, conflicts_with_all = ["closed", "maximal"])]
+    min_confidence: Option<f64>,
+    #[arg(long, requires = "min_confidence")]
+    min_lift: Option<f64>,
+    #[arg(long, requires = "min_confidence")]
+    min_leverage: Option<f64>,
+    #[arg(long, requires = "min_confidence")]
+    min_conviction: Option<f64>,
+    #[arg(long, requires = "min_confidence")]
+    min_all_confidence: Option<f64>,
+    #[arg(long, requires = "min_confidence")]
+    min_kulczynski: Option<f64>,
+    #[arg(long, requires = "min_confidence")]
+    min_cosine: Option<f64>,
+    #[arg(long, requires = "min_confidence")]
+    min_jaccard: Option<f64>,
+    /// Sort the rules from the best to the worst of support, confidence, lift, leverage,
+    /// conviction, all-confidence, kulczynski, cosine or jaccard
+    #[arg(long, requires = "min_confidence")]
+    sort_by: Option<Measure>,
+    /// Only output the first k rules
+    #[arg(long, requires = "sort_by")]
+    top_k: Option<usize>,
+    /// The pass at which candidate-distribution stops exchanging counts
+    #[arg(long, default_value_t = DEFAULT_SWITCH as u64, value_parser = value_parser!(u64).range(3..))]
+    switch_pass: u64,
+    /// The number of threads of threaded-count-distribution, or of each rank of
+    /// count-distribution, count-distribution-hybrid and count-distribution-tid, 0 for every core.
+    /// Defaults to every core without MPI and to 1 thread per rank.
+    /// Ranks only count with more than 1 thread if MPI provides Funneled threading.
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Split the database between the ranks of count-distribution, count-distribution-hybrid
+    /// and count-distribution-tid by equal-count, equal-items or cost-model.
+    /// Every rank then loads the whole input instead of only its byte range.
+    #[arg(long)]
+    partition: Option<PartitionStrategy>,
+    /// Save a checkpoint to this directory after every pass of apriori, apriori-hybrid,
+    /// count-distribution, count-distribution-hybrid and count-distribution-tid.
+    /// Fails if the directory already has one, which only --resume continues.
+    #[arg(long, conflicts_with_all = ["closed", "maximal", "min_confidence"])]
+    checkpoint: Option<PathBuf>,
+    /// Continue from the last checkpoint in this directory and keep saving checkpoints there.
+    /// The output keeps the itemsets written up to the checkpoint.
+    #[arg(long, conflicts_with_all = ["checkpoint", "closed", "maximal", "min_confidence"])]
+    resume: Option<PathBuf>,
+    /// What to do with lines of the input that are not transactions
+    #[arg(long, value_enum, default_value = "fail")]
+    bad_lines: BadLineMode,
+}
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum BadLineMode {
+    /// Stop with an error
+    Fail,
+    /// Leave them out
+    Skip,
+    /// Leave them out and report how many there were
+    Count,
+}
+
+impl Args {
+    /// Whether each rank only has to load its own byte range of the input
+    fn partitioned(&self) -> bool {
+        match self.algorithm {
+            Algorithms::CountDistribution
+            | Algorithms::CountDistributionHybrid
+            | Algorithms::CountDistributionTID => self.partition.is_none(),
+            Algorithms::CountDistributionAllReduce
+            | Algorithms::DataDistribution
+            | Algorithms::ParallelFPGrowth
+            | Algorithms::DistributedEclat => true,
+            _ => false,
+        }
+    }
+    /// Whether every rank runs the algorithm, and only rank 0 writes its output
+    fn mpi(&self) -> bool {
+        matches!(
+            self.algorithm,
+            Algorithms::CountDistribution
+                | Algorithms::CountDistributionHybrid
+                | Algorithms::CountDistributionTID
+                | Algorithms::CountDistributionAllReduce
+                | Algorithms::DataDistribution
+                | Algorithms::CandidateDistribution
+                | Algorithms::ParallelFPGrowth
+                | Algorithms::DistributedEclat
+        )
+    }
+    /// Where to save and resume the checkpoints, if anywhere
+    fn checkpoints(&self) -> Option<Checkpoints> {
+        self.checkpoint
+            .as_ref()
+            .or(self.resume.as_ref())
+            .map(Checkpoints::new)
+    }
+    /// The minimums of the rule measures that were given
+    fn thresholds(&self) -> Vec<(Measure, f64)> {
+        [
+            (Measure::Lift, self.min_lift),
+            (Measure::Leverage, self.min_leverage),
+            (Measure::Conviction, self.min_conviction),
+            (Measure::AllConfidence, self.min_all_confidence),
+            (Measure::Kulczynski, self.min_kulczynski),
+            (Measure::Cosine, self.min_cosine),
+            (Measure::Jaccard, self.min_jaccard),
+        ]
+        .into_iter()
+        .filter_map(|(m, min)| min.map(|min| (m, min)))
+        .collect()
+    }
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    let f: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if !(0.0..=1.0).contains(&f) {
+        return Err(format!("{f} is not between 0 and 1"));
+    }
+    Ok(f)
 }
 #[derive(Debug, Clone, ValueEnum)]
 pub enum Algorithms {
@@ -36,23 +178,42 @@ pub enum Algorithms {
     AprioriTID,
     AprioriHybrid,
     CountDistributionHybrid,
+    CountDistributionTID,
     AprioriTrie,
+    FPGrowth,
+    Eclat,
+    Declat,
+    Closed,
+    Maximal,
+    CountDistributionAllReduce,
+    DataDistribution,
+    CandidateDistribution,
+    ThreadedCountDistribution,
+    ParallelFPGrowth,
+    DistributedEclat,
 }
 
 pub struct Inputs<T: Write> {
     data: TransactionSet,
-    support_count: u64,
+    support_count: Support,
+    /// The checkpoint to continue after, which out already has the itemsets of
+    resume: Option<Checkpoint>,
     out: T,
 }
 
 impl<T: Write> Inputs<T> {
-    pub fn new(data: TransactionSet, support_count: u64, out: T) -> Self {
+    pub fn new(data: TransactionSet, support_count: Support, out: T) -> Self {
         Self {
             data,
             support_count,
+            resume: None,
             out,
         }
     }
+    pub fn with_resume(mut self, resume: Option<Checkpoint>) -> Self {
+        self.resume = resume;
+        self
+    }
 }
 #[derive(Default)]
 pub struct EmptyWriter();
@@ -62,55 +223,209 @@ impl EmptyWriter {
     }
 }
 impl Write for EmptyWriter {
-    fn write_set(&mut self, _: &[usize]) {}
+    fn write_set_with_support(&mut self, _: &[usize], _: u64) {}
+}
+impl WriteRule for EmptyWriter {
+    fn write_rule(&mut self, _: &Rule) {}
 }
 #[derive(Debug)]
 pub enum MainError {
     InvalidInputFile(std::io::Error),
+    InvalidInputData(DatError),
     InvalidOutputFile(std::io::Error),
     InvalidOutputCSV(std::io::Error),
+    InvalidCheckpoint(std::io::Error),
+    UnsupportedCheckpoint(Algorithms),
+    /// --checkpoint would overwrite the checkpoint in this directory
+    ExistingCheckpoint(PathBuf),
+    HelperFailed(HelperError),
 }
 
 static MPI_UNIVERSE: OnceLock<Universe> = OnceLock::new();
 
 pub fn get_universe() -> &'static Universe {
-    MPI_UNIVERSE.get_or_init(|| environment::initialize().expect("Failed to initialize MPI"))
+    MPI_UNIVERSE.get_or_init(|| {
+        let (universe, threading) = environment::initialize_with_threading(Threading::Funneled)
+            .expect("Failed to initialize MPI");
+        // The runners check the level themselves and count without threads below Funneled
+        if threading < Threading::Funneled && universe.world().rank() == 0 {
+            eprintln!(
+                "MPI only provides {threading:?} threading, so rank 0 counts before gathering"
+            );
+        }
+        universe
+    })
 }
 
 pub fn mpi_initialized() -> bool {
     MPI_UNIVERSE.get().is_some()
 }
 
-fn aa<T: Write>(mut input: Inputs<T>, v: &Args) {
+fn aa<T: Write>(mut input: Inputs<T>, v: &Args) -> Result<T, MainError> {
+    // The MPI runners resolve the support themselves from their partitions,
+    // and the partitioned ones only have the partition of their rank in input.data
+    let sup = input.data.support_count(input.support_count);
     match v.algorithm {
         Algorithms::Apriori => {
-            let runner = AprioriRunner::new(&input.data, input.support_count);
+            let mut runner = AprioriRunner::new(&input.data, sup);
+            if let Some(c) = v.checkpoints() {
+                runner = runner.with_checkpoints(c);
+            }
+            if let Some(c) = input.resume.take() {
+                runner = runner.with_resume(c);
+            }
             runner.run(&mut input.out);
         }
         Algorithms::CountDistribution => {
             let universe = get_universe();
-            let runner = CountDistribution::new(&input.data, input.support_count, &mut input.out);
-            runner.run(universe);
+            let runner = if v.partitioned() {
+                CountDistribution::from_partition(input.data, input.support_count, &mut input.out)
+            } else {
+                CountDistribution::new(&input.data, input.support_count, &mut input.out)
+                    .with_partition(v.partition.unwrap_or_default())
+            };
+            let mut runner = runner.with_threads(v.threads.unwrap_or(1));
+            if let Some(c) = v.checkpoints() {
+                runner = runner.with_checkpoints(c);
+            }
+            if let Some(c) = input.resume.take() {
+                runner = runner.with_resume(c);
+            }
+            runner.run(universe).map_err(MainError::HelperFailed)?;
         }
         Algorithms::AprioriTID => {
-            let runner = AprioriTIDRunner2::new(&input.data, input.support_count);
+            let runner = AprioriTIDRunner2::new(&input.data, sup);
             runner.run(&mut input.out);
         }
         Algorithms::AprioriHybrid => {
-            let runner = AprioriHybridRunner::new(&mut input.data, input.support_count);
+            let mut runner = AprioriHybridRunner::new(&mut input.data, sup);
+            if let Some(c) = v.checkpoints() {
+                runner = runner.with_checkpoints(c);
+            }
+            if let Some(c) = input.resume.take() {
+                runner = runner.with_resume(c);
+            }
             runner.run(&mut input.out);
         }
-        Algorithms::CountDistributionHybrid => {
+        Algorithms::CountDistributionHybrid | Algorithms::CountDistributionTID => {
             let universe = get_universe();
-            let runner =
-                CountDistributionHybrid::new(&input.data, input.support_count, &mut input.out);
-            runner.run(universe);
+            let runner = if v.partitioned() {
+                CountDistributionHybrid::from_partition(
+                    input.data,
+                    input.support_count,
+                    &mut input.out,
+                )
+            } else {
+                CountDistributionHybrid::new(&input.data, input.support_count, &mut input.out)
+                    .with_partition(v.partition.unwrap_or_default())
+            };
+            let switch = match v.algorithm {
+                Algorithms::CountDistributionTID => Switch::Always,
+                _ => Switch::Global,
+            };
+            let mut runner = runner
+                .with_threads(v.threads.unwrap_or(1))
+                .with_switch(switch);
+            if let Some(c) = v.checkpoints() {
+                runner = runner.with_checkpoints(c);
+            }
+            if let Some(c) = input.resume.take() {
+                runner = runner.with_resume(c);
+            }
+            runner.run(universe).map_err(MainError::HelperFailed)?;
         }
         Algorithms::AprioriTrie => {
-            let runner = AprioriTrie::new(input.data, input.support_count);
+            let runner = AprioriTrie::new(input.data, sup);
+            runner.run(&mut input.out);
+        }
+        Algorithms::FPGrowth => {
+            let runner = FPGrowth::new(&input.data, sup);
+            runner.run(&mut input.out);
+        }
+        Algorithms::Eclat => {
+            let runner = Eclat::new(&input.data, sup);
+            runner.run(&mut input.out);
+        }
+        Algorithms::Declat => {
+            let runner = DEclat::new(&input.data, sup);
+            runner.run(&mut input.out);
+        }
+        Algorithms::Closed => {
+            let runner = ClosedRunner::new(&input.data, sup);
+            runner.run(&mut input.out);
+        }
+        Algorithms::Maximal => {
+            let runner = MaximalRunner::new(&input.data, sup);
+            runner.run(&mut input.out);
+        }
+        Algorithms::CountDistributionAllReduce => {
+            let universe = get_universe();
+            let runner = CountDistributionAllReduce::from_partition(
+                input.data,
+                input.support_count,
+                &mut input.out,
+            );
+            let Ok(()) = runner.run(universe);
+        }
+        Algorithms::DataDistribution => {
+            let universe = get_universe();
+            let runner =
+                DataDistribution::from_partition(input.data, input.support_count, &mut input.out);
+            let Ok(()) = runner.run(universe);
+        }
+        Algorithms::CandidateDistribution => {
+            let universe = get_universe();
+            let runner =
+                CandidateDistribution::new(&input.data, input.support_count, &mut input.out)
+                    .with_switch(v.switch_pass as usize);
+            let Ok(()) = runner.run(universe);
+        }
+        Algorithms::ThreadedCountDistribution => {
+            let runner = ThreadedCountDistribution::new(&input.data, sup, v.threads.unwrap_or(0));
             runner.run(&mut input.out);
         }
+        Algorithms::ParallelFPGrowth => {
+            let universe = get_universe();
+            let runner =
+                ParallelFPGrowth::from_partition(input.data, input.support_count, &mut input.out);
+            let Ok(()) = runner.run(universe);
+        }
+        Algorithms::DistributedEclat => {
+            let universe = get_universe();
+            let runner =
+                DistributedEclat::from_partition(input.data, input.support_count, &mut input.out);
+            let Ok(()) = runner.run(universe);
+        }
+    }
+    Ok(input.out)
+}
+
+/// transactions is the size of the whole database, even if data is only a partition
+fn run<T: Write + WriteRule>(
+    data: TransactionSet,
+    transactions: u64,
+    a: &Args,
+    resume: Option<Checkpoint>,
+    mut out: T,
+) -> Result<(), MainError> {
+    if let Some(min_conf) = a.min_confidence {
+        let rules = aa(Inputs::new(data, a.support_count, RuleWriter::new()), a)?;
+        let mut filter = RuleFilter::new(a.thresholds(), a.sort_by, a.top_k);
+        rules.finish(min_conf, transactions, &mut filter);
+        filter.finish(&mut out);
+    } else if a.closed {
+        let closed = aa(Inputs::new(data, a.support_count, ClosedWriter::new()), a)?;
+        closed.finish(&mut out);
+    } else if a.maximal {
+        let maximal = aa(Inputs::new(data, a.support_count, MaximalWriter::new()), a)?;
+        maximal.finish(&mut out);
+    } else {
+        aa(
+            Inputs::new(data, a.support_count, out).with_resume(resume),
+            a,
+        )?;
     }
+    Ok(())
 }
 
 fn output_csv(file: &Path, percentage: f64, duration: &Duration) -> Result<(), MainError> {
@@ -129,36 +444,155 @@ fn output_csv(file: &Path, percentage: f64, duration: &Duration) -> Result<(), M
     Ok(())
 }
 
+/// The checkpoint in dir to resume from, or None if there is none yet.
+/// It has to be made with the support sup of this run.
+fn load_resume(dir: &Path, sup: u64) -> Result<Option<Checkpoint>, MainError> {
+    let Some(checkpoint) = Checkpoints::new(dir)
+        .load()
+        .map_err(MainError::InvalidCheckpoint)?
+    else {
+        return Ok(None);
+    };
+    if checkpoint.sup != sup {
+        return Err(MainError::InvalidCheckpoint(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "The checkpoint was made with support {} instead of {sup}",
+                checkpoint.sup
+            ),
+        )));
+    }
+    println!("Resuming after pass {}", checkpoint.pass);
+    Ok(Some(checkpoint))
+}
+
+/// Opens the output of a resumed run after the itemsets written up to the checkpoint,
+/// or creates it if there is no checkpoint yet
+fn open_resumed(file: &Path, checkpoint: Option<&Checkpoint>) -> Result<File, MainError> {
+    let Some(checkpoint) = checkpoint else {
+        return File::create(file).map_err(MainError::InvalidOutputFile);
+    };
+    let mut out = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(file)
+        .map_err(MainError::InvalidOutputFile)?;
+    // Each itemset is a line, and anything after the checkpoint gets written again
+    let mut reader = BufReader::new(&mut out);
+    let mut offset = 0;
+    let mut line = Vec::new();
+    for _ in 0..checkpoint.written {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(MainError::InvalidOutputFile)?;
+        if read == 0 || line.last() != Some(&b'\n') {
+            return Err(MainError::InvalidCheckpoint(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "The output has fewer itemsets than the checkpoint",
+            )));
+        }
+        offset += read as u64;
+    }
+    out.set_len(offset).map_err(MainError::InvalidOutputFile)?;
+    out.seek(SeekFrom::End(0))
+        .map_err(MainError::InvalidOutputFile)?;
+    Ok(out)
+}
+
 fn main() -> Result<(), MainError> {
-    let a = Args::parse();
-    let file = File::open(&a.file).map_err(MainError::InvalidInputFile)?;
-    let data = TransactionSet::from_dat(file);
-    let size = data.len();
+    let ran = try_main(Args::parse());
+    // The universe is static and never dropped, so MPI gets finalized here on every path
+    if mpi_initialized() {
+        unsafe { mpi::ffi::MPI_Finalize() };
+    }
+    ran
+}
+
+fn try_main(a: Args) -> Result<(), MainError> {
+    if a.checkpoints().is_some()
+        && !matches!(
+            a.algorithm,
+            Algorithms::Apriori
+                | Algorithms::AprioriHybrid
+                | Algorithms::CountDistribution
+                | Algorithms::CountDistributionHybrid
+                | Algorithms::CountDistributionTID
+        )
+    {
+        return Err(MainError::UnsupportedCheckpoint(a.algorithm));
+    }
+    let bad_lines = match a.bad_lines {
+        BadLineMode::Fail => BadLines::Fail,
+        BadLineMode::Skip | BadLineMode::Count => BadLines::Skip,
+    };
+    let (data, skipped, size) = if a.partitioned() {
+        let universe = get_universe();
+        let (data, skipped) =
+            load_partition(&a.file, bad_lines, universe).map_err(MainError::InvalidInputData)?;
+        let size = global_len(&data, universe);
+        (data, skipped, size)
+    } else {
+        let file = File::open(&a.file).map_err(MainError::InvalidInputFile)?;
+        let (data, skipped) =
+            TransactionSet::from_dat_with(file, bad_lines).map_err(MainError::InvalidInputData)?;
+        let size = data.len() as u64;
+        (data, skipped, size)
+    };
+    // The count is already the total over every rank
+    if let BadLineMode::Count = a.bad_lines
+        && (!mpi_initialized() || get_universe().world().rank() == 0)
+    {
+        println!("Skipped {skipped} bad lines");
+    }
+    let support_count = a.support_count.resolve(size);
+    if let Some(dir) = &a.checkpoint
+        && Checkpoints::new(dir).exists()
+    {
+        return Err(MainError::ExistingCheckpoint(dir.clone()));
+    }
+    let resume = match &a.resume {
+        Some(dir) => load_resume(dir, support_count)?,
+        None => None,
+    };
     let before = Instant::now();
-    match &a.output {
-        Some(f) => {
-            let out = File::create(f).map_err(MainError::InvalidOutputFile)?;
-            let writer = BufWriter::new(out);
-            let input = Inputs::new(data, a.support_count, writer);
-            aa(input, &a);
-        }
-        None => {
-            let input = Inputs::new(data, a.support_count, EmptyWriter::new());
-            aa(input, &a);
-        }
+    // Only rank 0 writes the itemsets, so the other ranks leave the output alone
+    let root = !a.mpi() || get_universe().world().rank() == 0;
+    let out = match (&a.output, &a.resume) {
+        (Some(f), Some(_)) if root => open_resumed(f, resume.as_ref()).map(Some),
+        (Some(f), None) if root => File::create(f)
+            .map(Some)
+            .map_err(MainError::InvalidOutputFile),
+        _ => Ok(None),
     };
+    // The other ranks would wait for rank 0 forever if it stopped here on its own
+    if a.mpi() {
+        let mut failed = out.is_err() as i32;
+        get_universe()
+            .world()
+            .process_at_rank(0)
+            .broadcast_into(&mut failed);
+        if failed != 0 && out.is_ok() {
+            return Err(MainError::InvalidOutputFile(std::io::Error::other(
+                "rank 0 could not open the output",
+            )));
+        }
+    }
+    match out? {
+        Some(out) => run(data, size, &a, resume, BufWriter::new(out)),
+        None => run(data, size, &a, resume, EmptyWriter::new()),
+    }?;
     if a.time {
         println!("Time Taken: {:?}", before.elapsed());
     }
-    if let Some(p) = a.csv {
-        if !mpi_initialized() || (mpi_initialized() && get_universe().world().rank() == 0) {
-            output_csv(
-                &p,
-                (a.support_count as f64) / (size as f64),
-                &before.elapsed(),
-            )?;
-        }
+    if let Some(p) = a.csv
+        && (!mpi_initialized() || (mpi_initialized() && get_universe().world().rank() == 0))
+    {
+        output_csv(
+            &p,
+            (support_count as f64) / (size as f64),
+            &before.elapsed(),
+        )?;
     }
-    unsafe { mpi::ffi::MPI_Finalize() };
     Ok(())
 }